@@ -1,16 +1,28 @@
-use std::process::exit;
+use std::{io::Write, process::exit};
 
 use anyhow::Result;
 use camino::*;
 use clap::{Parser, Subcommand};
+use log::Level;
 use offshape::{
-    export, load_config, show_parts, GlobalOptions, PullOptions, ShowPartsOptions,
+    check, export, find_config, init, list_documents, list_versions, list_workspaces,
+    load_config, release, schema, show_elements, show_parts, status, watch, CheckOptions,
+    GlobalOptions, InitOptions, ListDocumentContentsOptions, ListDocumentsOptions,
+    PullOptions, ReleaseOptions, ReporterKind, ShowElementsOptions, ShowPartsOptions,
+    StatusOptions, WatchOptions, CONFIG_FILE_NAME,
 };
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
-    #[arg(short, long = "config", value_name = "offshape.toml")]
+    /// The config to use. Defaults to the nearest offshape.toml in the current directory or
+    /// one of its parents.
+    #[arg(
+        short,
+        long = "config",
+        value_name = "offshape.toml",
+        env = "OFFSHAPE_CONFIG"
+    )]
     config_path: Option<Utf8PathBuf>,
 
     #[command(flatten)]
@@ -22,24 +34,203 @@ struct Cli {
 
 #[derive(Subcommand, Debug)]
 enum Commands {
-    /// Displays all OnShape parts found in the offshape.toml's [[part_studio]] tabs
+    /// Creates an offshape.toml from an OnShape document's URL, prompting for the part
+    /// studios to sync and where to write each format
+    Init(InitOptions),
+    /// Checks offshape.toml for mistakes, then verifies that the document, workspace and
+    /// part studios it refers to exist
+    Check(CheckOptions),
+    /// Prints a JSON Schema describing offshape.toml, for editors to validate and complete
+    /// it with
+    Schema,
+    /// Displays information about the document
+    #[command(subcommand)]
+    Show(ShowCommand),
+    /// Displays all OnShape parts found in the offshape.toml's [[part_studio]] tabs. Same
+    /// as `show parts`.
+    #[command(hide = true)]
     ShowParts(ShowPartsOptions),
     /// Pulls the latest CAD files (3mf, STL, STEP, etc) from OnShape, and write them to
     /// the paths found in offshape.toml
     Pull(PullOptions),
+    /// Compares the exported files on disk against the parts in OnShape, listing new,
+    /// changed and deleted parts, and files that don't belong to any part
+    Status(StatusOptions),
+    /// Watches the document for changes, pulling the parts that changed each time it does
+    Watch(WatchOptions),
+    /// Creates a named version of the document in OnShape, then pulls from that version
+    Release(ReleaseOptions),
+    /// Finds OnShape documents
+    #[command(subcommand)]
+    Documents(DocumentsCommand),
+    /// Lists the versions of an OnShape document
+    #[command(subcommand)]
+    Versions(VersionsCommand),
+    /// Lists the workspaces of an OnShape document
+    #[command(subcommand)]
+    Workspaces(WorkspacesCommand),
+}
+
+#[derive(Subcommand, Debug)]
+enum ShowCommand {
+    /// Displays all OnShape parts found in the offshape.toml's [[part_studio]] tabs
+    Parts(ShowPartsOptions),
+    /// Displays every tab in the document, grouped by type, noting those that are synced
+    Elements(ShowElementsOptions),
+}
+
+#[derive(Subcommand, Debug)]
+enum DocumentsCommand {
+    /// Lists the documents you can access, most recently modified first
+    List(ListDocumentsOptions),
+}
+
+#[derive(Subcommand, Debug)]
+enum VersionsCommand {
+    /// Lists a document's named versions
+    List(ListDocumentContentsOptions),
+}
+
+#[derive(Subcommand, Debug)]
+enum WorkspacesCommand {
+    /// Lists a document's workspaces
+    List(ListDocumentContentsOptions),
 }
 
-fn main() -> Result<()> {
-    let cli = Cli::parse();
-    let config_path = cli.config_path.unwrap_or("offshape.toml".into());
-    if !config_path.exists() {
-        eprintln!("offshape.toml not found");
-        exit(1);
+#[tokio::main]
+async fn main() -> Result<()> {
+    let mut cli = Cli::parse();
+    init_logging(&cli.global_options);
+    if cli.global_options.quiet {
+        quiet_reporter(&mut cli.command);
     }
 
+    // These commands don't need a config file
+    let command = match cli.command {
+        Commands::Init(options) => {
+            let config_path = cli.config_path.unwrap_or(CONFIG_FILE_NAME.into());
+            return init(&config_path, cli.global_options, options).await;
+        }
+        Commands::Schema => return schema(),
+        Commands::Documents(DocumentsCommand::List(options)) => {
+            return list_documents(cli.global_options, options).await
+        }
+        Commands::Versions(VersionsCommand::List(options)) => {
+            return list_versions(cli.global_options, options).await
+        }
+        Commands::Workspaces(WorkspacesCommand::List(options)) => {
+            return list_workspaces(cli.global_options, options).await
+        }
+        command => command,
+    };
+    let config_path = match cli.config_path {
+        Some(config_path) if !config_path.exists() => {
+            log::error!("{config_path} not found");
+            exit(1);
+        }
+        Some(config_path) => config_path,
+        None => match find_config() {
+            Some(config_path) => config_path,
+            None => {
+                log::error!(
+                    "{CONFIG_FILE_NAME} not found in the current directory or any of its \
+                     parents"
+                );
+                exit(1);
+            }
+        },
+    };
+
+    if let Commands::Check(options) = command {
+        return check(&config_path, cli.global_options, options).await;
+    }
     let config = load_config(&config_path)?;
-    match cli.command {
-        Commands::ShowParts(options) => show_parts(config, cli.global_options, options),
-        Commands::Pull(options) => export(config, cli.global_options, options),
+    match command {
+        Commands::ShowParts(options) | Commands::Show(ShowCommand::Parts(options)) => {
+            show_parts(config, cli.global_options, options).await
+        }
+        Commands::Show(ShowCommand::Elements(options)) => {
+            show_elements(config, cli.global_options, options).await
+        }
+        Commands::Pull(options) => export(config, cli.global_options, options).await,
+        Commands::Status(options) => status(config, cli.global_options, options).await,
+        Commands::Watch(options) => watch(config, cli.global_options, options).await,
+        Commands::Release(options) => release(config, cli.global_options, options).await,
+        Commands::Init(_) |
+        Commands::Check(_) |
+        Commands::Schema |
+        Commands::Documents(_) |
+        Commands::Versions(_) |
+        Commands::Workspaces(_) => unreachable!(),
+    }
+}
+
+/// Keeps `--quiet` pulls from drawing a progress bar, unless one was asked for. The log
+/// reporter describes progress at the info level, which `--quiet` hides.
+fn quiet_reporter(command: &mut Commands) {
+    let reporter = match command {
+        Commands::Pull(options) => &mut options.reporter,
+        Commands::Watch(options) => &mut options.reporter,
+        Commands::Release(options) => &mut options.reporter,
+        _ => return,
+    };
+    if *reporter == ReporterKind::Auto {
+        *reporter = ReporterKind::Log;
+    }
+}
+
+/// Logs to stderr at the level requested by the verbosity flags. The `OFFSHAPE_LOG`
+/// environment variable accepts `env_logger` filter directives for finer control.
+fn init_logging(global_options: &GlobalOptions) {
+    env_logger::Builder::new()
+        .filter_level(global_options.log_level())
+        .parse_env("OFFSHAPE_LOG")
+        .format(|f, record| match record.level() {
+            Level::Info => writeln!(f, "{}", record.args()),
+            level => writeln!(f, "{}: {}", level.as_str().to_lowercase(), record.args()),
+        })
+        .init();
+}
+
+#[cfg(test)]
+mod test {
+    use clap::{CommandFactory, Parser};
+
+    use super::*;
+
+    #[test]
+    fn test_cli_definition() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_global_options_follow_subcommands() {
+        let cli =
+            Cli::try_parse_from(["offshape", "pull", "-vv", "--replay", "cassette"]).unwrap();
+        assert_eq!(cli.global_options.verbose, 2);
+        assert_eq!(
+            cli.global_options.replay_dir.as_deref(),
+            Some("cassette".into())
+        );
+
+        let cli = Cli::try_parse_from(["offshape", "status", "-q"]).unwrap();
+        assert!(cli.global_options.quiet);
+    }
+
+    #[test]
+    fn test_quiet_pulls_have_no_progress_bar() {
+        let reporter = |args: &[&str]| {
+            let mut cli = Cli::try_parse_from(args).unwrap();
+            quiet_reporter(&mut cli.command);
+            match cli.command {
+                Commands::Pull(options) => options.reporter,
+                _ => unreachable!(),
+            }
+        };
+        assert_eq!(reporter(&["offshape", "pull", "-q"]), ReporterKind::Log);
+        assert_eq!(
+            reporter(&["offshape", "pull", "-q", "--reporter", "bar"]),
+            ReporterKind::Bar
+        );
     }
 }
@@ -0,0 +1,699 @@
+use std::fmt::{self, Display, Formatter};
+
+use anyhow::{anyhow, Result};
+use camino::{Utf8Path, Utf8PathBuf};
+use clap::Args;
+use serde::{
+    de::{MapAccess, SeqAccess, Visitor},
+    Deserialize, Deserializer,
+};
+use toml::Spanned;
+
+use crate::{
+    config::{read_config_files, ConfigFile, ConfigFormat, SyncConfig},
+    onshape::{environment_client, OnShapeClient},
+    parse_config,
+    schema::config_schema,
+    GlobalOptions,
+};
+
+#[derive(Args, Debug, Default)]
+pub struct CheckOptions {
+    /// Only checks the file itself, without verifying its contents against OnShape
+    #[arg(long)]
+    pub offline: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Severity {
+    Error,
+    Warning,
+}
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        })
+    }
+}
+
+/// The file, and line if known, that a problem was found on
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Location {
+    file: Utf8PathBuf,
+    line: Option<usize>,
+}
+impl Display for Location {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{line}", self.file),
+            None => write!(f, "{}", self.file),
+        }
+    }
+}
+
+/// A problem found in the config
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Diagnostic {
+    severity: Severity,
+    location: Location,
+    message: String,
+}
+impl Diagnostic {
+    fn error(location: Location, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            location,
+            message: message.into(),
+        }
+    }
+
+    fn warning(location: Location, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            location,
+            message: message.into(),
+        }
+    }
+}
+
+/// Verifies that the config at `config_path` is well formed, then that the document,
+/// workspace and part studios it refers to exist, printing each problem found
+pub async fn check(
+    config_path: &Utf8Path,
+    global_options: GlobalOptions,
+    options: CheckOptions,
+) -> Result<()> {
+    let files = read_config_files(config_path)?;
+    let config_dir: Utf8PathBuf = config_path.canonicalize_utf8()?.parent().unwrap().into();
+
+    let mut diagnostics = vec![];
+    let keys = ConfigKeys::parse(&files);
+    let config = check_files(&files, &keys, config_dir, &mut diagnostics);
+    if let (Some(config), false) = (config, options.offline) {
+        match environment_client(&global_options, config.base_url.as_deref()) {
+            Ok(client) => check_document(&client, &config, &keys, &mut diagnostics).await,
+            Err(e) => diagnostics.push(Diagnostic::error(
+                keys.locate(&[]),
+                format!(
+                    "Could not connect to OnShape to verify the config ({e}). Pass --offline \
+                     to only check the file."
+                ),
+            )),
+        }
+    }
+
+    for Diagnostic {
+        severity,
+        location,
+        message,
+    } in diagnostics.iter()
+    {
+        println!("{location}: {severity}: {message}");
+    }
+
+    let errors = diagnostics
+        .iter()
+        .filter(|d| d.severity == Severity::Error)
+        .count();
+    match errors {
+        0 => {
+            log::info!("{config_path} looks good");
+            Ok(())
+        }
+        1 => Err(anyhow!("Found a problem in {config_path}")),
+        n => Err(anyhow!("Found {n} problems in {config_path}")),
+    }
+}
+
+/// Checks the keys of each file, then the paths of the config they make up, returning the
+/// config if it could be parsed
+fn check_files(
+    files: &[ConfigFile],
+    keys: &ConfigKeys,
+    config_dir: Utf8PathBuf,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Option<SyncConfig> {
+    for file_keys in keys.files.iter() {
+        check_keys(file_keys, diagnostics);
+    }
+
+    let config = match parse_config(files, config_dir) {
+        Ok(config) => config,
+        Err(e) => {
+            let (location, message) = match e.downcast_ref::<toml::de::Error>() {
+                Some(e) => (
+                    type_error_location(files, keys),
+                    e.message().trim_end().into(),
+                ),
+                None => (keys.locate(&[]), format!("{e:#}")),
+            };
+            diagnostics.push(Diagnostic::error(location, message));
+            return None;
+        }
+    };
+
+    if config.output_dirs().is_empty() {
+        diagnostics.push(Diagnostic::error(
+            keys.locate(&[]),
+            "No output paths are configured, so nothing would be pulled. Set at least one \
+             of 3mf_path, step_path and stl_path.",
+        ));
+    }
+    // Each directory that will be written to, with its key
+    let mut dirs = vec![];
+    for (i, studio) in config.part_studios.iter().enumerate() {
+        for (format, path) in config.studio_outputs(studio) {
+            let key = format!("{}_path", format.extension());
+            let location = match studio.format_path(&format) {
+                Some(_) => keys.locate(&["part_studio", &i.to_string(), &key]),
+                None => keys.locate(&[&key]),
+            };
+            dirs.push((key, location, path));
+        }
+    }
+    if let Some(ref thumbnails) = config.thumbnails {
+        let location = keys.locate(&["thumbnails", "path"]);
+        dirs.push(("thumbnails.path".into(), location, thumbnails.path.clone()));
+    }
+
+    let mut checked_dirs = vec![];
+    for (key, location, path) in dirs {
+        if checked_dirs.contains(&path) {
+            continue;
+        }
+        if path.is_file() {
+            diagnostics.push(Diagnostic::error(
+                location,
+                format!("{key} is a file ({path}), but must be a directory"),
+            ));
+        } else if !path.exists() {
+            diagnostics.push(Diagnostic::warning(
+                location,
+                format!("{key} ({path}) doesn't exist yet. It will be created by pull."),
+            ));
+        }
+        checked_dirs.push(path);
+    }
+
+    Some(config)
+}
+
+/// Finds where a value of the wrong type was given. Merging the config's files loses the
+/// positions of their values, so a lone file is parsed again, in its own format, to find it.
+fn type_error_location(files: &[ConfigFile], keys: &ConfigKeys) -> Location {
+    match files {
+        [file] => {
+            let source = &file.source;
+            let line = match file.format {
+                ConfigFormat::Toml => toml::from_str::<SyncConfig>(source)
+                    .err()
+                    .and_then(|e| e.span())
+                    .map(|span| line_at(source, span.start)),
+                ConfigFormat::Json => serde_json::from_str::<SyncConfig>(source)
+                    .err()
+                    .map(|e| e.line())
+                    .filter(|line| *line > 0),
+                ConfigFormat::Yaml => serde_yaml::from_str::<SyncConfig>(source)
+                    .err()
+                    .and_then(|e| e.location())
+                    .map(|location| location.line()),
+            };
+            Location {
+                file: file.path.clone(),
+                line,
+            }
+        }
+        _ => keys.locate(&[]),
+    }
+}
+
+/// Reports the keys that offshape doesn't recognize, which would otherwise be ignored
+fn check_keys(keys: &FileKeys, diagnostics: &mut Vec<Diagnostic>) {
+    check_table(
+        keys,
+        &keys.tree,
+        &config_schema(),
+        "",
+        "the top level",
+        diagnostics,
+    );
+}
+
+/// Reports the keys of `table` that aren't properties of `schema`, then checks the tables
+/// nested within it. `path` is the table's dotted key, and `name` describes it in messages.
+fn check_table(
+    keys: &FileKeys,
+    table: &KeyTree,
+    schema: &serde_json::Value,
+    path: &str,
+    name: &str,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let Some(properties) = schema["properties"].as_object() else {
+        return;
+    };
+    for (key, value) in table.entries() {
+        let Some(property) = properties.get(&key.name) else {
+            diagnostics.push(Diagnostic::error(
+                keys.location(key.offset),
+                format!("Unknown key \"{}\" in {name}", key.name),
+            ));
+            continue;
+        };
+
+        let path = match path {
+            "" => key.name.clone(),
+            _ => format!("{path}.{}", key.name),
+        };
+        match value {
+            KeyTree::Table(_) => check_table(
+                keys,
+                value,
+                property,
+                &path,
+                &format!("[{path}]"),
+                diagnostics,
+            ),
+            KeyTree::Array(items) => {
+                for (i, item) in items.iter().enumerate() {
+                    let name = format!("[[{path}]] #{}", i + 1);
+                    check_table(keys, item, &property["items"], &path, &name, diagnostics);
+                }
+            }
+            KeyTree::Value => {}
+        }
+    }
+}
+
+/// Verifies that the document, its workspace and each part studio exist, and that each
+/// studio has parts
+async fn check_document(
+    client: &OnShapeClient,
+    config: &SyncConfig,
+    keys: &ConfigKeys<'_>,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    // The document is cloned so that its pinned version can be resolved
+    let mut document = config.document.clone();
+    let id_location = keys.locate(&["document", "id"]);
+    if let Err(e) = client.get_document(&document.id).await {
+        diagnostics.push(Diagnostic::error(
+            id_location,
+            format!("Could not find document {}: {e}", document.id),
+        ));
+        return;
+    }
+
+    let workspace_location = match keys.locate(&["document", "workspace_id"]) {
+        Location { line: None, .. } => id_location.clone(),
+        location => location,
+    };
+    match client.get_document_workspaces(&document.id).await {
+        Ok(workspaces) if !workspaces.iter().any(|w| w.id == document.workspace_id) => {
+            diagnostics.push(Diagnostic::error(
+                workspace_location,
+                format!(
+                    "Document {} has no workspace {}",
+                    document.id, document.workspace_id
+                ),
+            ));
+            return;
+        }
+        Ok(_) => {}
+        Err(e) => {
+            diagnostics.push(Diagnostic::error(
+                workspace_location,
+                format!("Could not list the document's workspaces: {e}"),
+            ));
+            return;
+        }
+    }
+
+    if document.needs_version() {
+        let version_location = keys.locate(&["document", "version"]);
+        let res = client
+            .get_document_versions(&document.id)
+            .await
+            .map_err(|e| anyhow!("Could not list the document's versions: {e}"))
+            .and_then(|versions| document.resolve_version(&versions));
+        if let Err(e) = res {
+            diagnostics.push(Diagnostic::error(version_location, e.to_string()));
+            return;
+        }
+    }
+
+    let elements = match client
+        .get_document_elements(&document.id, document.wvm())
+        .await
+    {
+        Ok(elements) => elements,
+        Err(e) => {
+            diagnostics.push(Diagnostic::error(
+                id_location,
+                format!("Could not list the document's tabs: {e}"),
+            ));
+            return;
+        }
+    };
+
+    for (i, studio) in config.part_studios.iter().enumerate() {
+        let index = i.to_string();
+        let location = match keys.locate(&["part_studio", &index, "name"]) {
+            Location { line: None, .. } => keys.locate(&["part_studio", &index, "id"]),
+            location => location,
+        };
+
+        // Studios are resolved one at a time, so that every missing studio is reported
+        let mut single = config.clone();
+        single.part_studios = vec![studio.clone()];
+        if let Err(e) = single.resolve_part_studios(&elements) {
+            diagnostics.push(Diagnostic::error(location, e.to_string()));
+            continue;
+        }
+
+        let studio = &single.part_studios[0];
+        match client
+            .get_studio_parts(&document.id, document.wvm(), &studio.id)
+            .await
+        {
+            Ok(parts) if parts.is_empty() => diagnostics.push(Diagnostic::warning(
+                location,
+                format!("Part studio \"{}\" has no parts", studio.display_name),
+            )),
+            Ok(_) => {}
+            Err(e) => diagnostics.push(Diagnostic::error(
+                location,
+                format!(
+                    "Could not list the parts of part studio \"{}\": {e}",
+                    studio.display_name
+                ),
+            )),
+        }
+    }
+}
+
+/// The 1-based line containing the byte at `offset`
+fn line_at(source: &str, offset: usize) -> usize {
+    source[..offset.min(source.len())].matches('\n').count() + 1
+}
+
+/// The keys of each file making up a config, so that the line a setting came from can be
+/// found
+struct ConfigKeys<'a> {
+    files: Vec<FileKeys<'a>>,
+}
+impl<'a> ConfigKeys<'a> {
+    fn parse(files: &'a [ConfigFile]) -> Self {
+        Self {
+            files: files.iter().map(FileKeys::parse).collect(),
+        }
+    }
+
+    /// Finds the key at `path` in the file that takes precedence over the others. Array
+    /// elements are selected by index. If no file has the key, the location is the
+    /// config's main file.
+    fn locate(&self, path: &[&str]) -> Location {
+        self.files
+            .iter()
+            .rev()
+            .find_map(|keys| {
+                let offset = keys.offset(path)?;
+                Some(keys.location(Some(offset)))
+            })
+            .unwrap_or_else(|| self.files.last().unwrap().location(None))
+    }
+}
+
+/// The keys of one file, and the source they were parsed from
+struct FileKeys<'a> {
+    file: &'a ConfigFile,
+    tree: KeyTree,
+}
+impl<'a> FileKeys<'a> {
+    /// Parses the file's keys. Only TOML keys have positions. Anything that can't be parsed
+    /// results in an empty tree, as the problem is reported when parsing the config itself.
+    fn parse(file: &'a ConfigFile) -> Self {
+        let tree = match file.format {
+            ConfigFormat::Toml => toml::from_str(&file.source).unwrap_or_default(),
+            ConfigFormat::Json | ConfigFormat::Yaml => KeyTree::from_table(&file.table),
+        };
+        Self { file, tree }
+    }
+
+    /// The position of the key at `path`
+    fn offset(&self, path: &[&str]) -> Option<usize> {
+        let mut node = &self.tree;
+        let mut offset = None;
+        for segment in path {
+            node = match node {
+                KeyTree::Array(items) => items.get(segment.parse::<usize>().ok()?)?,
+                _ => {
+                    let (key, value) =
+                        node.entries().iter().find(|(k, _)| k.name == *segment)?;
+                    offset = key.offset;
+                    value
+                }
+            };
+        }
+        offset
+    }
+
+    fn location(&self, offset: Option<usize>) -> Location {
+        Location {
+            file: self.file.path.clone(),
+            line: offset.map(|offset| line_at(&self.file.source, offset)),
+        }
+    }
+}
+
+/// A key, and the position in the file's source where it was found
+#[derive(Clone, Debug)]
+struct Key {
+    name: String,
+    offset: Option<usize>,
+}
+
+#[derive(Clone, Debug, Default)]
+enum KeyTree {
+    Table(Vec<(Key, KeyTree)>),
+    Array(Vec<KeyTree>),
+    #[default]
+    Value,
+}
+impl KeyTree {
+    /// The keys of an already parsed table, which have no positions
+    fn from_table(table: &toml::Table) -> Self {
+        fn from_value(value: &toml::Value) -> KeyTree {
+            match value {
+                toml::Value::Table(table) => KeyTree::from_table(table),
+                toml::Value::Array(items) => {
+                    KeyTree::Array(items.iter().map(from_value).collect())
+                }
+                _ => KeyTree::Value,
+            }
+        }
+
+        KeyTree::Table(
+            table
+                .iter()
+                .map(|(name, value)| {
+                    let key = Key {
+                        name: name.clone(),
+                        offset: None,
+                    };
+                    (key, from_value(value))
+                })
+                .collect(),
+        )
+    }
+
+    fn entries(&self) -> &[(Key, KeyTree)] {
+        match self {
+            KeyTree::Table(entries) => entries,
+            _ => &[],
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for KeyTree {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(KeyTreeVisitor)
+    }
+}
+
+struct KeyTreeVisitor;
+impl<'de> Visitor<'de> for KeyTreeVisitor {
+    type Value = KeyTree;
+
+    fn expecting(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str("a TOML value")
+    }
+
+    fn visit_bool<E>(self, _: bool) -> Result<KeyTree, E> {
+        Ok(KeyTree::Value)
+    }
+
+    fn visit_i64<E>(self, _: i64) -> Result<KeyTree, E> {
+        Ok(KeyTree::Value)
+    }
+
+    fn visit_u64<E>(self, _: u64) -> Result<KeyTree, E> {
+        Ok(KeyTree::Value)
+    }
+
+    fn visit_f64<E>(self, _: f64) -> Result<KeyTree, E> {
+        Ok(KeyTree::Value)
+    }
+
+    fn visit_str<E>(self, _: &str) -> Result<KeyTree, E> {
+        Ok(KeyTree::Value)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<KeyTree, A::Error> {
+        let mut items = vec![];
+        while let Some(item) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(KeyTree::Array(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<KeyTree, A::Error> {
+        let mut entries = vec![];
+        while let Some(key) = map.next_key::<Spanned<String>>()? {
+            let key = Key {
+                offset: Some(key.span().start),
+                name: key.into_inner(),
+            };
+            entries.push((key, map.next_value()?));
+        }
+        Ok(KeyTree::Table(entries))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use indoc::indoc;
+
+    use super::*;
+
+    #[test]
+    fn test_check_files() {
+        let base = indoc! {r#"
+            stl_path = "."
+            colour = "red"
+
+            [document]
+            id = "6a1de9567f16cd7d1f564dbb"
+            workspace_id = "da2053ce82593768f41daab4"
+        "#};
+        let main = indoc! {r#"
+            include = ["base.toml"]
+
+            [document]
+            workspace_id = "da2053ce82593768f41daab4"
+
+            [[part_studio]]
+            name = "Printables"
+
+            [[part_studio]]
+            name = "Hardware"
+            export = true
+        "#};
+        let file = |path: &str, source: &str| ConfigFile {
+            path: path.into(),
+            format: ConfigFormat::Toml,
+            source: source.into(),
+            table: toml::from_str(source).unwrap(),
+        };
+        let files = [file("base.toml", base), file("offshape.toml", main)];
+        let keys = ConfigKeys::parse(&files);
+        let mut diagnostics = vec![];
+        let config = check_files(&files, &keys, ".".into(), &mut diagnostics);
+
+        assert!(config.is_some());
+        let location = |file: &str, line| Location {
+            file: file.into(),
+            line: Some(line),
+        };
+        assert_eq!(
+            diagnostics,
+            vec![
+                Diagnostic::error(
+                    location("base.toml", 2),
+                    "Unknown key \"colour\" in the top level"
+                ),
+                Diagnostic::error(
+                    location("offshape.toml", 11),
+                    "Unknown key \"export\" in [[part_studio]] #2"
+                ),
+            ]
+        );
+        assert_eq!(keys.locate(&["document", "id"]), location("base.toml", 5));
+        assert_eq!(
+            keys.locate(&["document", "workspace_id"]),
+            location("offshape.toml", 4)
+        );
+        assert_eq!(
+            keys.locate(&["part_studio", "1", "name"]),
+            location("offshape.toml", 10)
+        );
+    }
+
+    #[test]
+    fn test_type_error_location() {
+        let file = |path: &str, format, source: &str| ConfigFile {
+            path: path.into(),
+            format,
+            source: source.into(),
+            table: format.parse(source).unwrap(),
+        };
+        let json = file(
+            "offshape.json",
+            ConfigFormat::Json,
+            indoc! {r#"
+                {
+                  "stl_path": ".",
+                  "document": {
+                    "id": "6a1de9567f16cd7d1f564dbb",
+                    "workspace_id": "da2053ce82593768f41daab4"
+                  },
+                  "part_studio": [
+                    { "name": "Printables", "display_name": 3 }
+                  ]
+                }
+            "#},
+        );
+        let yaml = file(
+            "offshape.yaml",
+            ConfigFormat::Yaml,
+            indoc! {r#"
+                stl_path: .
+                document:
+                  id: 6a1de9567f16cd7d1f564dbb
+                  workspace_id: da2053ce82593768f41daab4
+                part_studio:
+                  - name: Printables
+                    display_name: [3]
+            "#},
+        );
+
+        for (file, line) in [(json, 8), (yaml, 7)] {
+            let files = [file];
+            let keys = ConfigKeys::parse(&files);
+            let mut diagnostics = vec![];
+            let config = check_files(&files, &keys, ".".into(), &mut diagnostics);
+
+            assert!(config.is_none());
+            assert_eq!(diagnostics.len(), 1, "{diagnostics:?}");
+            assert_eq!(
+                diagnostics[0].location,
+                Location {
+                    file: files[0].path.clone(),
+                    line: Some(line),
+                }
+            );
+        }
+    }
+}
@@ -1,38 +1,761 @@
-use camino::Utf8Path;
-use clap::Args;
-use serde::Deserialize;
+use std::{collections::HashMap, env, fs};
+
+use anyhow::{anyhow, Context, Result};
+use camino::{Utf8Path, Utf8PathBuf};
+use clap::{ArgAction, Args};
+use itertools::Itertools;
+use log::LevelFilter;
+use schemars::JsonSchema;
+use serde::{de::Error as _, Deserialize, Deserializer};
 use url::Url;
 
-use crate::onshape::models::ExportFileFormat;
+use crate::onshape::{
+    models::{DocumentElement, ExportFileFormat, TabElementType, ThumbnailSize, Version},
+    DocumentId, DocumentUrl, ElementId, OnShapeClient, WorkspaceId, Wvm,
+};
+
+/// The name of the config file that offshape looks for
+pub const CONFIG_FILE_NAME: &str = "offshape.toml";
+/// The names of configs written in other languages, which are looked for, in order, when
+/// there's no offshape.toml
+const OTHER_CONFIG_FILE_NAMES: [&str; 3] = ["offshape.json", "offshape.yaml", "offshape.yml"];
+
+/// Finds the nearest offshape.toml (or offshape.json, or offshape.yaml) in the current
+/// directory or one of its ancestors
+pub fn find_config() -> Option<Utf8PathBuf> {
+    let dir = Utf8PathBuf::try_from(env::current_dir().ok()?).ok()?;
+    dir.ancestors()
+        .flat_map(|dir| {
+            [CONFIG_FILE_NAME]
+                .into_iter()
+                .chain(OTHER_CONFIG_FILE_NAMES)
+                .map(|name| dir.join(name))
+        })
+        .find(|path| path.is_file())
+}
+
+/// The languages a config can be written in, chosen by the file's extension
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ConfigFormat {
+    Toml,
+    Json,
+    Yaml,
+}
+impl ConfigFormat {
+    pub fn from_path(path: &Utf8Path) -> Result<Self> {
+        match path.extension().map(|e| e.to_lowercase()).as_deref() {
+            Some("json") => Ok(ConfigFormat::Json),
+            Some("yaml" | "yml") => Ok(ConfigFormat::Yaml),
+            _ => Ok(ConfigFormat::Toml),
+        }
+    }
+
+    pub fn parse(&self, source: &str) -> Result<toml::Table> {
+        Ok(match self {
+            ConfigFormat::Toml => toml::from_str(source)?,
+            ConfigFormat::Json => serde_json::from_str(source)?,
+            ConfigFormat::Yaml => serde_yaml::from_str(source)?,
+        })
+    }
+}
+
+/// One of the files making up a config
+#[derive(Clone, Debug)]
+pub(crate) struct ConfigFile {
+    pub path: Utf8PathBuf,
+    pub format: ConfigFormat,
+    pub source: String,
+    pub table: toml::Table,
+}
+
+/// Reads the config at `path`, along with the files it includes, and those they include.
+/// Included files come before the file including them, as later files take precedence.
+pub(crate) fn read_config_files(path: &Utf8Path) -> Result<Vec<ConfigFile>> {
+    let mut files = vec![];
+    read_config_file(path, &mut vec![], &mut files)?;
+    Ok(files)
+}
+
+fn read_config_file(
+    path: &Utf8Path,
+    including: &mut Vec<Utf8PathBuf>,
+    files: &mut Vec<ConfigFile>,
+) -> Result<()> {
+    let canonical_path = path
+        .canonicalize_utf8()
+        .with_context(|| format!("Could not read {path}"))?;
+    if let Some(start) = including.iter().position(|p| *p == canonical_path) {
+        let cycle = including[start..]
+            .iter()
+            .chain([&canonical_path])
+            .join(" -> ");
+        return Err(anyhow!(
+            "Config files include each other in a cycle: {cycle}"
+        ));
+    }
+    let format = ConfigFormat::from_path(path)?;
+    let source =
+        fs::read_to_string(path).with_context(|| format!("Could not read {path}"))?;
+    let table = format
+        .parse(&source)
+        .with_context(|| format!("In {path}"))?;
+
+    // Included paths are relative to the file that includes them
+    let dir = path.parent().unwrap_or(Utf8Path::new(""));
+    let include_error = || anyhow!("In {path}, include must be a list of paths");
+    let includes = match table.get("include") {
+        None => vec![],
+        Some(toml::Value::Array(includes)) => includes
+            .iter()
+            .map(|include| {
+                include
+                    .as_str()
+                    .map(|p| dir.join(p))
+                    .ok_or_else(include_error)
+            })
+            .collect::<Result<_>>()?,
+        Some(_) => return Err(include_error()),
+    };
+
+    including.push(canonical_path);
+    for include in includes {
+        read_config_file(&include, including, files)?;
+    }
+    including.pop();
+
+    files.push(ConfigFile {
+        path: path.into(),
+        format,
+        source,
+        table,
+    });
+    Ok(())
+}
+
+/// Rewrites the relative output paths set by each of `files` to be relative to the
+/// directory of the last, the config that includes the others, as a path is relative to the
+/// file that sets it. Variables must already have been expanded.
+pub(crate) fn rebase_paths(files: &mut [ConfigFile]) {
+    let Some(config_dir) = files
+        .last()
+        .and_then(|f| f.path.parent())
+        .map(Utf8PathBuf::from)
+    else {
+        return;
+    };
+    for file in files.iter_mut() {
+        let dir = file.path.parent().unwrap_or(Utf8Path::new(""));
+        // Included paths are joined onto the including file's directory, so an included
+        // file's directory starts with the config's, unless it was included by absolute path
+        let dir = dir.strip_prefix(&config_dir).unwrap_or(dir);
+        if dir.as_str().is_empty() {
+            continue;
+        }
+
+        let table = &mut file.table;
+        rebase_path_keys(table, dir, &["3mf_path", "step_path", "stl_path"]);
+        if let Some(toml::Value::Array(studios)) = table.get_mut("part_studio") {
+            for studio in studios.iter_mut().filter_map(|s| s.as_table_mut()) {
+                rebase_path_keys(studio, dir, &["3mf_path", "step_path", "stl_path"]);
+            }
+        }
+        if let Some(toml::Value::Table(thumbnails)) = table.get_mut("thumbnails") {
+            rebase_path_keys(thumbnails, dir, &["path"]);
+        }
+    }
+}
+
+fn rebase_path_keys(table: &mut toml::Table, dir: &Utf8Path, keys: &[&str]) {
+    for key in keys {
+        if let Some(toml::Value::String(path)) = table.get_mut(*key) {
+            *path = dir.join(&*path).into_string();
+        }
+    }
+}
+
+/// Merges the tables of `files`, with later files taking precedence. Tables are merged key
+/// by key, while any other value, including an array, replaces the value it overrides.
+pub(crate) fn merge_config_files(files: &[ConfigFile]) -> toml::Table {
+    let mut merged = toml::Table::new();
+    for file in files {
+        merge_table(&mut merged, file.table.clone());
+    }
+    merged.remove("include");
+    merged
+}
+
+fn merge_table(base: &mut toml::Table, overrides: toml::Table) {
+    for (key, value) in overrides {
+        let value = match (base.remove(&key), value) {
+            (Some(toml::Value::Table(mut table)), toml::Value::Table(overrides)) => {
+                merge_table(&mut table, overrides);
+                toml::Value::Table(table)
+            }
+            (_, value) => value,
+        };
+        base.insert(key, value);
+    }
+}
+
+/// Replaces `${VAR}` and `${VAR:-default}` in the table's strings with the values of
+/// environment variables. Hooks are left alone, as the shell expands their variables when
+/// they run.
+pub(crate) fn interpolate_env(table: &mut toml::Table) -> Result<()> {
+    for (key, value) in table.iter_mut() {
+        if key != "hooks" {
+            interpolate_value(value, key)?;
+        }
+    }
+    Ok(())
+}
+
+fn interpolate_value(value: &mut toml::Value, key: &str) -> Result<()> {
+    match value {
+        toml::Value::String(s) => {
+            *s = interpolate(s, |name| env::var(name).ok())
+                .with_context(|| format!("In {key}"))?
+        }
+        toml::Value::Array(items) => {
+            for item in items.iter_mut() {
+                interpolate_value(item, key)?;
+            }
+        }
+        toml::Value::Table(table) => {
+            for (k, v) in table.iter_mut() {
+                interpolate_value(v, &format!("{key}.{k}"))?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Expands the variables in `value`, looking each up with `lookup`. As in the shell, a
+/// default replaces a variable that is unset or empty.
+fn interpolate(value: &str, lookup: impl Fn(&str) -> Option<String>) -> Result<String> {
+    let mut expanded = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        expanded.push_str(&rest[..start]);
+        let end = start +
+            rest[start..]
+                .find('}')
+                .ok_or_else(|| anyhow!("\"{value}\" has a ${{ without a closing }}"))?;
+        let (name, default) = match rest[start + 2..end].split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (&rest[start + 2..end], None),
+        };
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(anyhow!(
+                "\"{name}\" is not a valid environment variable name"
+            ));
+        }
+
+        match default {
+            Some(default) => expanded.push_str(
+                &lookup(name)
+                    .filter(|v| !v.is_empty())
+                    .unwrap_or_else(|| default.into()),
+            ),
+            None => expanded.push_str(&lookup(name).ok_or_else(|| {
+                anyhow!("The environment variable {name} is not set, and has no default")
+            })?),
+        }
+        rest = &rest[end + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
 
 #[derive(Args, Clone, Debug)]
 pub struct GlobalOptions {
-    #[arg(short, long = "proxy", value_name = "PROXY_URL")]
+    #[arg(short, long = "proxy", value_name = "PROXY_URL", global = true)]
     pub proxy_url: Option<Url>,
+
+    /// Records every request made to OnShape, and its response, into a cassette directory
+    #[arg(
+        long = "record",
+        value_name = "DIR",
+        conflicts_with = "replay_dir",
+        global = true
+    )]
+    pub record_dir: Option<Utf8PathBuf>,
+
+    /// Replays the responses in a cassette directory instead of contacting OnShape
+    #[arg(long = "replay", value_name = "DIR", global = true)]
+    pub replay_dir: Option<Utf8PathBuf>,
+
+    /// Logs more detail. Pass once to log each HTTP request, and twice to include headers.
+    #[arg(
+        short,
+        long,
+        action = ArgAction::Count,
+        conflicts_with = "quiet",
+        global = true
+    )]
+    pub verbose: u8,
+
+    /// Only logs warnings and errors
+    #[arg(short, long, global = true)]
+    pub quiet: bool,
+}
+impl GlobalOptions {
+    pub fn log_level(&self) -> LevelFilter {
+        match (self.quiet, self.verbose) {
+            (true, _) => LevelFilter::Warn,
+            (false, 0) => LevelFilter::Info,
+            (false, 1) => LevelFilter::Debug,
+            (false, _) => LevelFilter::Trace,
+        }
+    }
 }
 
-#[derive(Clone, Debug, Deserialize)]
+// The schema printed by `offshape schema` is generated from this type and those it contains,
+// so their doc comments are shown to users
+
+/// Describes the OnShape parts that offshape pulls, and where it writes them. Strings may
+/// refer to environment variables as ${VAR} or ${VAR:-default}.
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
 pub struct SyncConfig {
+    /// The directory 3MF files are written to
     #[serde(rename = "3mf_path")]
+    #[schemars(with = "Option<String>")]
     pub three_mf_path: Option<Box<Utf8Path>>,
+    /// The directory STEP files are written to
+    #[schemars(with = "Option<String>")]
     pub step_path: Option<Box<Utf8Path>>,
+    /// The directory STL files are written to
+    #[schemars(with = "Option<String>")]
     pub stl_path: Option<Box<Utf8Path>>,
+    /// The root of the OnShape REST API, like `https://acme.onshape.com/api`. Defaults to
+    /// that of cad.onshape.com.
+    pub base_url: Option<String>,
 
     pub document: SyncedDocument,
+    /// The part studios whose parts are pulled
     #[serde(rename = "part_studio")]
     pub part_studios: Vec<SyncedPartStudio>,
+    #[serde(default)]
+    pub hooks: Hooks,
+    pub thumbnails: Option<Thumbnails>,
+
+    /// The directory containing the config file, where offshape keeps its record of what
+    /// was last pulled
+    #[serde(skip)]
+    pub root: Utf8PathBuf,
 }
 impl SyncConfig {
-    pub fn export_formats(&self) -> Vec<&ExportFileFormat> {
+    pub fn format_path(&self, format: &ExportFileFormat) -> Option<Box<Utf8Path>> {
+        match format {
+            ExportFileFormat::ThreeMF => self.three_mf_path.clone(),
+            ExportFileFormat::Step => self.step_path.clone(),
+            ExportFileFormat::Stl => self.stl_path.clone(),
+        }
+    }
+
+    /// The formats a studio's parts are exported to, each with the directory it's written
+    /// to. A studio's own paths override the config's, and its `formats` limit which are
+    /// exported.
+    pub fn studio_outputs(
+        &self,
+        studio: &SyncedPartStudio,
+    ) -> Vec<(ExportFileFormat, Box<Utf8Path>)> {
         ExportFileFormat::iter()
-            .filter(|f| match **f {
-                ExportFileFormat::ThreeMF => self.three_mf_path.as_deref().is_some(),
-                ExportFileFormat::Step => self.step_path.as_deref().is_some(),
-                ExportFileFormat::Stl => self.stl_path.as_deref().is_some(),
+            .filter(|f| {
+                studio
+                    .formats
+                    .as_ref()
+                    .is_none_or(|formats| formats.contains(f))
+            })
+            .filter_map(|f| {
+                Some((*f, studio.format_path(f).or_else(|| self.format_path(f))?))
             })
             .collect()
     }
 
+    /// Every directory that some studio's parts are exported to, with the format written to
+    /// it
+    pub fn output_dirs(&self) -> Vec<(ExportFileFormat, Box<Utf8Path>)> {
+        let mut dirs = vec![];
+        for studio in self.part_studios.iter() {
+            for output in self.studio_outputs(studio) {
+                if !dirs.contains(&output) {
+                    dirs.push(output);
+                }
+            }
+        }
+        dirs
+    }
+
+    /// Verifies that every format a studio exports has a directory to be written to
+    pub(crate) fn check_studio_formats(&self) -> Result<()> {
+        for studio in self.part_studios.iter() {
+            for format in studio.formats.iter().flatten() {
+                if studio.format_path(format).is_none() && self.format_path(format).is_none()
+                {
+                    let ext = format.extension();
+                    return Err(anyhow!(
+                        "[[part_studio]] {} exports {ext}, but neither it nor the config sets \
+                         {ext}_path",
+                        studio.label()
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Replaces any OnShape URLs found in ID fields with the IDs they contain, verifying
+    /// that every URL refers to the configured document, and its workspace or version
+    pub(crate) fn resolve_urls(&mut self) -> Result<()> {
+        let document = &mut self.document;
+        let mut document_url_workspace = None;
+        if DocumentUrl::is_url(&document.id) {
+            let url: DocumentUrl = document.id.parse().context("In [document] id")?;
+            check_not_microversion(&url, "[document] id")?;
+            // A version's URL pins the config to that version
+            match (url.version_id, &document.pinned_version) {
+                (Some(url_version), None) => {
+                    document.pinned_version = Some(url_version.into_string());
+                }
+                (Some(url_version), Some(version)) if *url_version != **version => {
+                    return Err(anyhow!(
+                        "[document] id is a URL for version {url_version}, but version is \
+                         \"{version}\""
+                    ));
+                }
+                _ => {}
+            }
+            document.id = url.document_id;
+            document_url_workspace = url.workspace_id;
+        }
+
+        if DocumentUrl::is_url(&document.workspace_id) {
+            let url: DocumentUrl = document
+                .workspace_id
+                .parse()
+                .context("In [document] workspace_id")?;
+            check_same_document(&document.id, &url, "[document] workspace_id")?;
+            check_not_microversion(&url, "[document] workspace_id")?;
+            document.workspace_id = url.workspace_id.ok_or_else(|| {
+                anyhow!("[document] workspace_id is a URL that doesn't include a workspace")
+            })?;
+        }
+
+        match document_url_workspace {
+            Some(url_workspace) if document.workspace_id.is_empty() => {
+                document.workspace_id = url_workspace;
+            }
+            Some(url_workspace) if url_workspace != document.workspace_id => {
+                return Err(anyhow!(
+                    "[document] id is a URL for workspace {url_workspace}, but workspace_id is \
+                     {}",
+                    document.workspace_id
+                ));
+            }
+            _ => {}
+        }
+        if document.workspace_id.is_empty() {
+            return Err(anyhow!(
+                "[document] is missing workspace_id. Add it, or set id to a URL that includes \
+                 the workspace."
+            ));
+        }
+
+        for studio in self.part_studios.iter_mut() {
+            if studio.id.is_empty() && studio.name.is_none() {
+                return Err(anyhow!(
+                    "Every [[part_studio]] needs an id or a name, but {} has neither",
+                    studio.label()
+                ));
+            }
+            if !DocumentUrl::is_url(&studio.id) {
+                continue;
+            }
+
+            let field = format!("[[part_studio]] {} id", studio.label());
+            let url: DocumentUrl =
+                studio.id.parse().with_context(|| format!("In {field}"))?;
+            check_same_document(&self.document.id, &url, &field)?;
+            check_not_microversion(&url, &field)?;
+            match (url.version_id, &self.document.pinned_version) {
+                (Some(url_version), None) => {
+                    return Err(anyhow!(
+                        "{field} is a URL for version {url_version}, but [document] doesn't \
+                         set a version. Set [document] version to {url_version}, or use the \
+                         URL of the tab in the workspace."
+                    ));
+                }
+                (Some(url_version), Some(version)) if *url_version != **version => {
+                    return Err(anyhow!(
+                        "{field} is a URL for version {url_version}, but [document] version is \
+                         \"{version}\""
+                    ));
+                }
+                _ => {}
+            }
+            match url.workspace_id {
+                Some(ref url_workspace) if *url_workspace != self.document.workspace_id => {
+                    return Err(anyhow!(
+                        "{field} is a URL for workspace {url_workspace}, but the configured \
+                         workspace is {}",
+                        self.document.workspace_id
+                    ));
+                }
+                _ => {}
+            }
+            studio.id = url.element_id.ok_or_else(|| {
+                anyhow!("{field} is a URL that doesn't include a tab (/e/...)")
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Matches each configured part studio against the document's tabs, filling in the IDs
+    /// of studios configured by name, and the display names of those that lack one
+    pub fn resolve_part_studios(
+        &mut self,
+        elements: &HashMap<ElementId, DocumentElement>,
+    ) -> Result<()> {
+        for studio in self.part_studios.iter_mut() {
+            let element = match studio.name {
+                Some(ref name) => {
+                    let mut matches: Vec<&DocumentElement> = elements
+                        .values()
+                        .filter(|e| e.element_type == TabElementType::PartStudio)
+                        .filter(|e| e.name == *name)
+                        .sorted_by(|a, b| a.id.cmp(&b.id))
+                        .collect();
+                    // An ID disambiguates studios that share a name
+                    if !studio.id.is_empty() && matches.iter().any(|e| e.id == studio.id) {
+                        matches.retain(|e| e.id == studio.id);
+                    }
+                    match matches[..] {
+                        [] => {
+                            return Err(anyhow!(
+                                "Could not find a part studio named \"{name}\". The \
+                                 document's part studios are: {}",
+                                elements
+                                    .values()
+                                    .filter(|e| e.element_type == TabElementType::PartStudio)
+                                    .map(|e| format!("\"{}\"", e.name))
+                                    .sorted()
+                                    .join(", ")
+                            ))
+                        }
+                        [element] => element,
+                        _ => {
+                            return Err(anyhow!(
+                                "More than one part studio is named \"{name}\" ({}). Add \
+                                 the id of the intended studio to its [[part_studio]].",
+                                matches.iter().map(|e| e.id.as_str()).join(", ")
+                            ))
+                        }
+                    }
+                }
+                None => {
+                    let element = elements.get(&studio.id).ok_or_else(|| {
+                        anyhow!("Could not find a part studio ({})", studio.id)
+                    })?;
+                    if element.element_type != TabElementType::PartStudio {
+                        return Err(anyhow!(
+                            "[[part_studio]] {} is the tab \"{}\", which isn't a part studio",
+                            studio.id,
+                            element.name
+                        ));
+                    }
+                    element
+                }
+            };
+
+            if !studio.id.is_empty() && studio.id != element.id {
+                return Err(anyhow!(
+                    "[[part_studio]] {} has id {}, but the part studio with that name has id \
+                     {}",
+                    studio.label(),
+                    studio.id,
+                    element.id
+                ));
+            }
+            studio.id = element.id.clone();
+            if studio.display_name.is_empty() {
+                studio.display_name = element.name.clone();
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns a copy of the config whose pinned version has been looked up, and whose part
+    /// studios have been resolved against the document's tabs. See
+    /// [`SyncedDocument::resolve_version`] and [`SyncConfig::resolve_part_studios`].
+    pub async fn resolved(&self, client: &OnShapeClient) -> Result<SyncConfig> {
+        let mut config = self.clone();
+        if config.document.needs_version() {
+            let versions = client.get_document_versions(&config.document.id).await?;
+            config.document.resolve_version(&versions)?;
+        }
+        let elements = client
+            .get_document_elements(&config.document.id, config.document.wvm())
+            .await?;
+        config.resolve_part_studios(&elements)?;
+        Ok(config)
+    }
+}
+
+/// Rejects URLs of microversions, which offshape can't pull from
+fn check_not_microversion(url: &DocumentUrl, field: &str) -> Result<()> {
+    if let Some(ref microversion_id) = url.microversion_id {
+        return Err(anyhow!(
+            "{field} is a URL for microversion {microversion_id}, which offshape can't pull \
+             from. Use the URL of the document's workspace, or of one of its versions."
+        ));
+    }
+    Ok(())
+}
+
+fn check_same_document(
+    document_id: &DocumentId,
+    url: &DocumentUrl,
+    field: &str,
+) -> Result<()> {
+    if url.document_id != *document_id {
+        return Err(anyhow!(
+            "{field} is a URL for document {}, but the configured document is {document_id}",
+            url.document_id
+        ));
+    }
+    Ok(())
+}
+
+/// The document to pull from. Either field may be given as the URL of the document, as
+/// copied from the browser, in which case `workspace_id` may be omitted.
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
+pub struct SyncedDocument {
+    /// The document's ID, or its URL. The URL of a version sets `version`.
+    #[schemars(with = "String")]
+    pub id: DocumentId,
+    /// The workspace to pull from. May be omitted if id is a URL that includes the
+    /// workspace.
+    #[serde(default)]
+    #[schemars(with = "String")]
+    pub workspace_id: WorkspaceId,
+    /// A version to pull from instead of the workspace's current state, given as the
+    /// version's ID or its name
+    #[serde(rename = "version")]
+    pub pinned_version: Option<String>,
+    /// The version that parts are pulled from. Set when releasing, or once `version` has
+    /// been resolved.
+    #[serde(skip)]
+    pub version: Option<Version>,
+}
+impl SyncedDocument {
+    /// Whether `version` names a version that hasn't been looked up yet
+    pub fn needs_version(&self) -> bool {
+        self.version.is_none() && self.pinned_version.is_some()
+    }
+
+    /// Finds the version named by `version` among the document's `versions`, matching
+    /// either its ID or its name
+    pub fn resolve_version(&mut self, versions: &[Version]) -> Result<()> {
+        let Some(ref pinned) = self.pinned_version else {
+            return Ok(());
+        };
+        let matches: Vec<&Version> = match versions.iter().find(|v| v.id.as_str() == pinned) {
+            Some(version) => vec![version],
+            None => versions.iter().filter(|v| v.name == *pinned).collect(),
+        };
+        match matches[..] {
+            [] => Err(anyhow!(
+                "[document] version is \"{pinned}\", but the document has no version with that \
+                 ID or name. Its versions are: {}",
+                versions.iter().map(|v| format!("\"{}\"", v.name)).join(", ")
+            )),
+            [version] => {
+                self.version = Some(version.clone());
+                Ok(())
+            }
+            _ => Err(anyhow!(
+                "More than one version is named \"{pinned}\" ({}). Set [document] version to \
+                 the ID of the intended version.",
+                matches.iter().map(|v| v.id.as_str()).join(", ")
+            )),
+        }
+    }
+
+    /// The state of the document that parts are read from
+    pub fn wvm(&self) -> Wvm {
+        match self.version {
+            Some(ref version) => Wvm::from(&version.id),
+            None => Wvm::from(&self.workspace_id),
+        }
+    }
+}
+
+/// Shell commands run once a pull has written its files. They are run from the directory
+/// containing offshape.toml, and a failing hook fails the pull.
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
+pub struct Hooks {
+    /// Run for each file written, with OFFSHAPE_FILE, OFFSHAPE_PART_NAME, OFFSHAPE_PART_ID
+    /// and OFFSHAPE_FORMAT describing it
+    pub after_file: Option<String>,
+    /// Run once per pull, with OFFSHAPE_FILES listing the files written, one per line
+    pub after_pull: Option<String>,
+}
+
+/// Images of each pulled part, and of each part studio, written alongside the exports.
+/// They aren't run through hooks, or removed when their parts are.
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
+pub struct Thumbnails {
+    /// The directory the images are written to
+    #[schemars(with = "String")]
+    pub path: Box<Utf8Path>,
+    /// The sizes each image is rendered at, as WIDTHxHEIGHT
+    #[serde(default = "Thumbnails::default_sizes")]
+    #[schemars(schema_with = "crate::schema::thumbnail_sizes")]
+    pub sizes: Vec<ThumbnailSize>,
+}
+impl Thumbnails {
+    fn default_sizes() -> Vec<ThumbnailSize> {
+        vec![ThumbnailSize {
+            width: 300,
+            height: 300,
+        }]
+    }
+}
+
+/// A part studio whose parts are pulled. The studio is identified by `id`, by `name`, or by
+/// both.
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
+pub struct SyncedPartStudio {
+    /// How the studio is described in offshape's output. Defaults to the studio's tab name.
+    #[serde(default)]
+    pub display_name: String,
+    /// The part studio's element ID, or the URL of the part studio's tab
+    #[serde(default)]
+    #[schemars(with = "String")]
+    pub id: ElementId,
+    /// The name of the part studio's tab in OnShape
+    pub name: Option<String>,
+
+    /// The formats the studio's parts are exported to, like ["3mf", "stl"]. Defaults to
+    /// every format that has a path.
+    #[serde(default, deserialize_with = "deserialize_formats")]
+    #[schemars(schema_with = "crate::schema::export_formats")]
+    pub formats: Option<Vec<ExportFileFormat>>,
+    /// Overrides the config's 3mf_path for this studio
+    #[serde(rename = "3mf_path")]
+    #[schemars(with = "Option<String>")]
+    pub three_mf_path: Option<Box<Utf8Path>>,
+    /// Overrides the config's step_path for this studio
+    #[schemars(with = "Option<String>")]
+    pub step_path: Option<Box<Utf8Path>>,
+    /// Overrides the config's stl_path for this studio
+    #[schemars(with = "Option<String>")]
+    pub stl_path: Option<Box<Utf8Path>>,
+}
+impl SyncedPartStudio {
     pub fn format_path(&self, format: &ExportFileFormat) -> Option<Box<Utf8Path>> {
         match format {
             ExportFileFormat::ThreeMF => self.three_mf_path.clone(),
@@ -40,22 +763,618 @@ impl SyncConfig {
             ExportFileFormat::Stl => self.stl_path.clone(),
         }
     }
-}
 
-#[derive(Debug, Deserialize)]
-pub struct ExportFormat {
-    pub format: ExportFileFormat,
-    pub path: Box<Utf8Path>,
+    /// Describes the studio in error messages, using whatever identifying information is
+    /// available before it has been resolved
+    fn label(&self) -> String {
+        match (self.display_name.as_str(), self.name.as_deref()) {
+            ("", Some(name)) => format!("\"{name}\""),
+            ("", None) => self.id.to_string(),
+            (display_name, _) => format!("\"{display_name}\""),
+        }
+    }
 }
 
-#[derive(Clone, Debug, Deserialize)]
-pub struct SyncedDocument {
-    pub id: String,
-    pub workspace_id: String,
+/// Reads formats by their extensions, ignoring case
+fn deserialize_formats<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<Vec<ExportFileFormat>>, D::Error> {
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|name| {
+            ExportFileFormat::iter()
+                .find(|f| f.extension().eq_ignore_ascii_case(name))
+                .copied()
+                .ok_or_else(|| {
+                    D::Error::custom(format!(
+                        "unknown format \"{name}\", expected 3mf, step or stl"
+                    ))
+                })
+        })
+        .collect::<std::result::Result<_, _>>()
+        .map(Some)
 }
 
-#[derive(Clone, Debug, Deserialize)]
-pub struct SyncedPartStudio {
-    pub display_name: String,
-    pub id: String,
+#[cfg(test)]
+mod test {
+    use indoc::indoc;
+
+    use super::*;
+    use crate::{onshape::VersionId, test_fixtures::TempRoot};
+
+    const DOCUMENT_URL: &str =
+        "https://cad.onshape.com/documents/6a1de9567f16cd7d1f564dbb/w/\
+                                da2053ce82593768f41daab4";
+
+    fn parse(toml: &str) -> Result<SyncConfig> {
+        let mut config: SyncConfig = toml::from_str(toml)?;
+        config.resolve_urls()?;
+        Ok(config)
+    }
+
+    #[test]
+    fn test_resolve_urls() {
+        let config = parse(&format!(
+            indoc! {r#"
+                [document]
+                id = "{document_url}"
+
+                [[part_studio]]
+                display_name = "Printables"
+                id = "{document_url}/e/59628be740b2cf41a42e4c8a"
+            "#},
+            document_url = DOCUMENT_URL
+        ))
+        .unwrap();
+
+        assert_eq!(config.document.id.as_str(), "6a1de9567f16cd7d1f564dbb");
+        assert_eq!(
+            config.document.workspace_id.as_str(),
+            "da2053ce82593768f41daab4"
+        );
+        assert_eq!(
+            config.part_studios[0].id.as_str(),
+            "59628be740b2cf41a42e4c8a"
+        );
+    }
+
+    #[test]
+    fn test_resolve_urls_rejects_other_documents() {
+        let err = parse(&format!(
+            indoc! {r#"
+                [document]
+                id = "{document_url}"
+
+                [[part_studio]]
+                display_name = "Printables"
+                id = "{other_document_url}/e/59628be740b2cf41a42e4c8a"
+            "#},
+            document_url = DOCUMENT_URL,
+            other_document_url =
+                DOCUMENT_URL.replace("6a1de9567f16cd7d1f564dbb", "aaaaaaaaaaaaaaaaaaaaaaaa"),
+        ))
+        .unwrap_err();
+        assert!(
+            err.to_string().contains("aaaaaaaaaaaaaaaaaaaaaaaa"),
+            "{err}"
+        );
+
+        assert!(parse(indoc! {r#"
+            part_studio = []
+
+            [document]
+            id = "6a1de9567f16cd7d1f564dbb"
+        "#})
+        .is_err());
+    }
+
+    #[test]
+    fn test_resolve_version_urls() {
+        let version_url = DOCUMENT_URL.replace("/w/", "/v/");
+        let config = parse(&format!(
+            indoc! {r#"
+                [document]
+                id = "{version_url}"
+                workspace_id = "0e1f2a3b4c5d6e7f8a9b0c1d"
+
+                [[part_studio]]
+                display_name = "Printables"
+                id = "{version_url}/e/59628be740b2cf41a42e4c8a"
+            "#},
+            version_url = version_url
+        ))
+        .unwrap();
+        assert_eq!(
+            config.document.pinned_version.as_deref(),
+            Some("da2053ce82593768f41daab4")
+        );
+        assert_eq!(
+            config.part_studios[0].id.as_str(),
+            "59628be740b2cf41a42e4c8a"
+        );
+
+        // A studio's version must be the document's
+        let err = parse(&format!(
+            indoc! {r#"
+                [document]
+                id = "{document_url}"
+
+                [[part_studio]]
+                display_name = "Printables"
+                id = "{version_url}/e/59628be740b2cf41a42e4c8a"
+            "#},
+            document_url = DOCUMENT_URL,
+            version_url = version_url
+        ))
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "[[part_studio]] \"Printables\" id is a URL for version da2053ce82593768f41daab4, but \
+             [document] doesn't set a version. Set [document] version to \
+             da2053ce82593768f41daab4, or use the URL of the tab in the workspace."
+        );
+
+        let err = parse(&format!(
+            indoc! {r#"
+                part_studio = []
+
+                [document]
+                id = "{version_url}"
+                workspace_id = "0e1f2a3b4c5d6e7f8a9b0c1d"
+                version = "v1.0"
+            "#},
+            version_url = version_url
+        ))
+        .unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("[document] id is a URL for version"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_resolve_urls_rejects_microversions() {
+        let microversion_url = format!("{DOCUMENT_URL}/m/0d1e2f3a4b5c6d7e8f9a0b1c");
+        let err = parse(&format!(
+            indoc! {r#"
+                part_studio = []
+
+                [document]
+                id = "{microversion_url}"
+            "#},
+            microversion_url = microversion_url
+        ))
+        .unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("[document] id is a URL for microversion"),
+            "{err}"
+        );
+
+        let err = parse(&format!(
+            indoc! {r#"
+                [document]
+                id = "{document_url}"
+
+                [[part_studio]]
+                display_name = "Printables"
+                id = "{microversion_url}/e/59628be740b2cf41a42e4c8a"
+            "#},
+            document_url = DOCUMENT_URL,
+            microversion_url = microversion_url
+        ))
+        .unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("[[part_studio]] \"Printables\" id is a URL for microversion"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_resolve_part_studios_by_name() {
+        let element = |id: &str, name: &str, element_type| {
+            (
+                ElementId::from(id),
+                DocumentElement {
+                    id: id.into(),
+                    name: name.into(),
+                    file_name: None,
+                    element_type,
+                },
+            )
+        };
+        let elements: HashMap<ElementId, DocumentElement> = [
+            element(
+                "59628be740b2cf41a42e4c8a",
+                "Printables",
+                TabElementType::PartStudio,
+            ),
+            element(
+                "0d4b5a6e8c1f2a3b4c5d6e7f",
+                "Hardware",
+                TabElementType::PartStudio,
+            ),
+            element(
+                "1e5c6b7f9d2a3b4c5d6e7f80",
+                "Hardware",
+                TabElementType::PartStudio,
+            ),
+            element(
+                "2f6d7c8a0e3b4c5d6e7f8091",
+                "Assembly 1",
+                TabElementType::Assembly,
+            ),
+        ]
+        .into();
+
+        let mut config = parse(indoc! {r#"
+            [document]
+            id = "6a1de9567f16cd7d1f564dbb"
+            workspace_id = "da2053ce82593768f41daab4"
+
+            [[part_studio]]
+            name = "Printables"
+        "#})
+        .unwrap();
+        config.resolve_part_studios(&elements).unwrap();
+        assert_eq!(
+            config.part_studios[0].id.as_str(),
+            "59628be740b2cf41a42e4c8a"
+        );
+        assert_eq!(config.part_studios[0].display_name, "Printables");
+
+        let mut config = parse(indoc! {r#"
+            [document]
+            id = "6a1de9567f16cd7d1f564dbb"
+            workspace_id = "da2053ce82593768f41daab4"
+
+            [[part_studio]]
+            name = "Hardware"
+        "#})
+        .unwrap();
+        let err = config.resolve_part_studios(&elements).unwrap_err();
+        assert!(err.to_string().contains("More than one"), "{err}");
+
+        let mut config = parse(indoc! {r#"
+            [document]
+            id = "6a1de9567f16cd7d1f564dbb"
+            workspace_id = "da2053ce82593768f41daab4"
+
+            [[part_studio]]
+            name = "Hardware"
+            id = "1e5c6b7f9d2a3b4c5d6e7f80"
+        "#})
+        .unwrap();
+        config.resolve_part_studios(&elements).unwrap();
+        assert_eq!(
+            config.part_studios[0].id.as_str(),
+            "1e5c6b7f9d2a3b4c5d6e7f80"
+        );
+
+        // Tabs of other types can't be pulled from
+        let mut config = parse(indoc! {r#"
+            [document]
+            id = "6a1de9567f16cd7d1f564dbb"
+            workspace_id = "da2053ce82593768f41daab4"
+
+            [[part_studio]]
+            id = "2f6d7c8a0e3b4c5d6e7f8091"
+        "#})
+        .unwrap();
+        let err = config.resolve_part_studios(&elements).unwrap_err();
+        assert!(err.to_string().contains("isn't a part studio"), "{err}");
+    }
+
+    #[test]
+    fn test_include_cycle() {
+        let dir = TempRoot::new();
+        fs::write(dir.join("offshape.toml"), r#"include = ["a.toml"]"#).unwrap();
+        fs::write(dir.join("a.toml"), r#"include = ["b.toml"]"#).unwrap();
+        fs::write(dir.join("b.toml"), r#"include = ["a.toml"]"#).unwrap();
+
+        let err = read_config_files(&dir.join("offshape.toml")).unwrap_err();
+        let dir = &*dir;
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "Config files include each other in a cycle: {dir}/a.toml -> {dir}/b.toml -> \
+                 {dir}/a.toml"
+            )
+        );
+    }
+
+    #[test]
+    fn test_merge_config_files() {
+        let file = |source: &str| ConfigFile {
+            path: "offshape.toml".into(),
+            format: ConfigFormat::Toml,
+            source: source.into(),
+            table: toml::from_str(source).unwrap(),
+        };
+        let base = file(indoc! {r#"
+            stl_path = "export/stl"
+
+            [document]
+            id = "6a1de9567f16cd7d1f564dbb"
+            workspace_id = "da2053ce82593768f41daab4"
+
+            [[part_studio]]
+            name = "Hardware"
+        "#});
+        let project = file(indoc! {r#"
+            include = ["../base.toml"]
+            stl_path = "stl"
+
+            [document]
+            workspace_id = "0e1f2a3b4c5d6e7f8a9b0c1d"
+
+            [[part_studio]]
+            name = "Printables"
+        "#});
+
+        let config: SyncConfig = toml::Value::Table(merge_config_files(&[base, project]))
+            .try_into()
+            .unwrap();
+        assert_eq!(config.stl_path.as_deref(), Some(Utf8Path::new("stl")));
+        assert_eq!(config.document.id.as_str(), "6a1de9567f16cd7d1f564dbb");
+        assert_eq!(
+            config.document.workspace_id.as_str(),
+            "0e1f2a3b4c5d6e7f8a9b0c1d"
+        );
+        assert_eq!(config.part_studios.len(), 1);
+        assert_eq!(config.part_studios[0].name.as_deref(), Some("Printables"));
+    }
+
+    #[test]
+    fn test_included_paths_are_relative_to_their_file() {
+        let file = |path: &str, source: &str| ConfigFile {
+            path: path.into(),
+            format: ConfigFormat::Toml,
+            source: source.into(),
+            table: toml::from_str(source).unwrap(),
+        };
+        let base = file(
+            "project/../shared/base.toml",
+            indoc! {r#"
+                base_url = "https://acme.onshape.com/api"
+                stl_path = "stl"
+                step_path = "/srv/step"
+
+                [[part_studio]]
+                name = "Hardware"
+                3mf_path = "hardware/3mf"
+
+                [thumbnails]
+                path = "images"
+            "#},
+        );
+        let project = file(
+            "project/offshape.toml",
+            indoc! {r#"
+                include = ["../shared/base.toml"]
+                3mf_path = "3mf"
+
+                [document]
+                id = "6a1de9567f16cd7d1f564dbb"
+                workspace_id = "da2053ce82593768f41daab4"
+            "#},
+        );
+
+        let mut files = [base, project];
+        rebase_paths(&mut files);
+        let config: SyncConfig = toml::Value::Table(merge_config_files(&files))
+            .try_into()
+            .unwrap();
+        assert_eq!(
+            config.base_url.as_deref(),
+            Some("https://acme.onshape.com/api")
+        );
+        fn path(path: &Option<Box<Utf8Path>>) -> Option<&str> {
+            path.as_deref().map(Utf8Path::as_str)
+        }
+        assert_eq!(path(&config.three_mf_path), Some("3mf"));
+        assert_eq!(path(&config.stl_path), Some("../shared/stl"));
+        assert_eq!(path(&config.step_path), Some("/srv/step"));
+        assert_eq!(
+            path(&config.part_studios[0].three_mf_path),
+            Some("../shared/hardware/3mf")
+        );
+        assert_eq!(config.thumbnails.unwrap().path.as_str(), "../shared/images");
+    }
+
+    #[test]
+    fn test_yaml_config() {
+        let toml_source = indoc! {r#"
+            3mf_path = "export/3mf"
+            stl_path = "export/stl"
+
+            [document]
+            id = "6a1de9567f16cd7d1f564dbb"
+            workspace_id = "da2053ce82593768f41daab4"
+
+            [[part_studio]]
+            name = "Printables"
+            formats = ["stl"]
+        "#};
+        let table = ConfigFormat::Toml.parse(toml_source).unwrap();
+        let yaml_source = serde_yaml::to_string(&table).unwrap();
+        assert_eq!(ConfigFormat::Yaml.parse(&yaml_source).unwrap(), table);
+
+        let yaml_source = indoc! {r#"
+            3mf_path: export/3mf
+            stl_path: export/stl
+            document:
+              id: 6a1de9567f16cd7d1f564dbb
+              workspace_id: da2053ce82593768f41daab4
+            part_studio:
+              - name: Printables
+                formats: [stl]
+        "#};
+        assert_eq!(ConfigFormat::Yaml.parse(yaml_source).unwrap(), table);
+        assert_eq!(
+            ConfigFormat::from_path(Utf8Path::new("offshape.yml")).unwrap(),
+            ConfigFormat::Yaml
+        );
+    }
+
+    #[test]
+    fn test_interpolate() {
+        let lookup = |name: &str| match name {
+            "WORKSPACE" => Some("da2053ce82593768f41daab4".to_string()),
+            "EMPTY" => Some("".to_string()),
+            _ => None,
+        };
+
+        assert_eq!(
+            interpolate("${WORKSPACE}", lookup).unwrap(),
+            "da2053ce82593768f41daab4"
+        );
+        assert_eq!(
+            interpolate("export/${KIND:-stl}/${EMPTY}", lookup).unwrap(),
+            "export/stl/"
+        );
+        assert_eq!(interpolate("${EMPTY:-main}", lookup).unwrap(), "main");
+        assert_eq!(interpolate("no variables", lookup).unwrap(), "no variables");
+        assert!(interpolate("${MISSING}", lookup).is_err());
+        assert!(interpolate("${WORKSPACE", lookup).is_err());
+        assert!(interpolate("${BAD NAME}", lookup).is_err());
+    }
+
+    #[test]
+    fn test_interpolate_version_and_base_url() {
+        env::set_var("OFFSHAPE_TEST_RELEASE", "v1.2");
+        let mut table = ConfigFormat::Toml
+            .parse(indoc! {r#"
+                base_url = "${OFFSHAPE_TEST_BASE_URL:-https://acme.onshape.com/api}"
+                part_studio = []
+
+                [document]
+                id = "6a1de9567f16cd7d1f564dbb"
+                workspace_id = "da2053ce82593768f41daab4"
+                version = "${OFFSHAPE_TEST_RELEASE}"
+            "#})
+            .unwrap();
+        interpolate_env(&mut table).unwrap();
+        let config: SyncConfig = toml::Value::Table(table).try_into().unwrap();
+
+        assert_eq!(
+            config.base_url.as_deref(),
+            Some("https://acme.onshape.com/api")
+        );
+        assert_eq!(config.document.pinned_version.as_deref(), Some("v1.2"));
+    }
+
+    #[test]
+    fn test_resolve_version() {
+        let version = |id: &str, name: &str| Version {
+            id: id.into(),
+            name: name.into(),
+            description: None,
+            document_id: "6a1de9567f16cd7d1f564dbb".into(),
+            microversion_id: format!("mv-{id}"),
+        };
+        let versions = [
+            version("0a0a0a0a0a0a0a0a0a0a0a0a", "v1.0"),
+            version("1b1b1b1b1b1b1b1b1b1b1b1b", "v1.1"),
+            version("2c2c2c2c2c2c2c2c2c2c2c2c", "v1.1"),
+        ];
+        let resolve = |pinned: &str| {
+            let mut document = parse(&format!(
+                indoc! {r#"
+                    part_studio = []
+
+                    [document]
+                    id = "6a1de9567f16cd7d1f564dbb"
+                    workspace_id = "da2053ce82593768f41daab4"
+                    version = "{}"
+                "#},
+                pinned
+            ))
+            .unwrap()
+            .document;
+            document.resolve_version(&versions).map(|_| document)
+        };
+
+        let document = resolve("v1.0").unwrap();
+        assert_eq!(
+            document.wvm(),
+            Wvm::from(&VersionId::from("0a0a0a0a0a0a0a0a0a0a0a0a"))
+        );
+        let document = resolve("2c2c2c2c2c2c2c2c2c2c2c2c").unwrap();
+        assert_eq!(document.version.unwrap().name, "v1.1");
+
+        let err = resolve("v1.1").unwrap_err();
+        assert!(err.to_string().contains("More than one version"), "{err}");
+        let err = resolve("v2.0").unwrap_err();
+        assert!(err.to_string().contains("\"v1.0\""), "{err}");
+    }
+
+    #[test]
+    fn test_studio_outputs() {
+        let config = parse(indoc! {r#"
+            3mf_path = "3mf"
+            step_path = "step"
+
+            [document]
+            id = "6a1de9567f16cd7d1f564dbb"
+            workspace_id = "da2053ce82593768f41daab4"
+
+            [[part_studio]]
+            name = "Printables"
+            formats = ["3MF", "stl"]
+            stl_path = "printables/stl"
+
+            [[part_studio]]
+            name = "Hardware"
+            formats = ["step"]
+
+            [[part_studio]]
+            name = "Fixtures"
+        "#})
+        .unwrap();
+        let outputs = |i: usize| -> Vec<(ExportFileFormat, String)> {
+            config
+                .studio_outputs(&config.part_studios[i])
+                .into_iter()
+                .map(|(format, dir)| (format, dir.to_string()))
+                .collect()
+        };
+
+        assert_eq!(
+            outputs(0),
+            [
+                (ExportFileFormat::ThreeMF, "3mf".into()),
+                (ExportFileFormat::Stl, "printables/stl".into())
+            ]
+        );
+        assert_eq!(outputs(1), [(ExportFileFormat::Step, "step".into())]);
+        assert_eq!(
+            outputs(2),
+            [
+                (ExportFileFormat::ThreeMF, "3mf".into()),
+                (ExportFileFormat::Step, "step".into())
+            ]
+        );
+        config.check_studio_formats().unwrap();
+
+        let config = parse(indoc! {r#"
+            step_path = "step"
+
+            [document]
+            id = "6a1de9567f16cd7d1f564dbb"
+            workspace_id = "da2053ce82593768f41daab4"
+
+            [[part_studio]]
+            name = "Printables"
+            formats = ["stl"]
+        "#})
+        .unwrap();
+        let err = config.check_studio_formats().unwrap_err();
+        assert!(
+            err.to_string().contains("neither it nor the config"),
+            "{err}"
+        );
+    }
 }
@@ -0,0 +1,249 @@
+use std::{collections::BTreeMap, process::Output};
+
+use anyhow::{anyhow, Context, Result};
+use camino::{Utf8Path, Utf8PathBuf};
+use indoc::formatdoc;
+use itertools::Itertools;
+use tokio::process::Command;
+
+use crate::{
+    config::SyncConfig,
+    onshape::models::Document,
+    pull::PullSummary,
+    state::{relative_path, PullState},
+};
+
+/// Fails if anything is staged in the git index of the repository containing `dir`, as it
+/// would be swept into offshape's commit
+pub(crate) async fn ensure_nothing_staged(dir: &Utf8Path) -> Result<()> {
+    let output = git(dir, ["diff", "--cached", "--quiet"]).await?;
+    match output.status.code() {
+        Some(0) => Ok(()),
+        Some(1) => Err(anyhow!(
+            "There are changes staged in git. Commit or unstage them before pulling with \
+             --commit."
+        )),
+        _ => Err(git_error("diff --cached", &output)),
+    }
+}
+
+/// Stages the files written and deleted by a pull, along with the record of the pull, and
+/// commits them with a message describing the parts that changed
+pub(crate) async fn commit_pull(
+    config: &SyncConfig,
+    summary: &PullSummary,
+    document: &Document,
+    microversion: &str,
+) -> Result<()> {
+    let root = &config.root;
+    let state_path = PullState::path(root);
+
+    let mut add = vec!["add".to_string(), "--".into(), state_path.to_string()];
+    add.extend(summary.written.iter().map(|p| p.to_string()));
+    add.extend(summary.images.iter().map(|p| p.to_string()));
+    check("add", git(root, &add).await?)?;
+    if !summary.deleted.is_empty() {
+        let mut rm = vec![
+            "rm".to_string(),
+            "--cached".into(),
+            "--ignore-unmatch".into(),
+            "--quiet".into(),
+            "--".into(),
+        ];
+        rm.extend(summary.deleted.iter().map(|p| p.to_string()));
+        check("rm", git(root, &rm).await?)?;
+    }
+
+    if git(root, ["diff", "--cached", "--quiet"])
+        .await?
+        .status
+        .success()
+    {
+        log::info!("Nothing changed, so nothing was committed");
+        return Ok(());
+    }
+
+    let message = commit_message(
+        document,
+        &config.document.workspace_id,
+        microversion,
+        &PullState::load(root)?,
+        root,
+        summary,
+    );
+    check(
+        "commit",
+        git(root, ["commit", "--quiet", "-m", &message]).await?,
+    )?;
+    log::info!("Committed the pulled files");
+    Ok(())
+}
+
+fn commit_message(
+    document: &Document,
+    workspace_id: &str,
+    microversion: &str,
+    state: &PullState,
+    root: &Utf8Path,
+    summary: &PullSummary,
+) -> String {
+    // Each part's files, keyed by the part's name and ID
+    let mut changed_parts: BTreeMap<(String, String), Vec<Utf8PathBuf>> = BTreeMap::new();
+    for path in summary.written.iter() {
+        let path = relative_path(root, path);
+        let key = match state.files.get(&path) {
+            Some(record) => (record.part_name.clone(), record.part_id.to_string()),
+            None => ("Unknown part".into(), "?".into()),
+        };
+        changed_parts.entry(key).or_default().push(path);
+    }
+
+    let mut sections = vec![];
+    if !changed_parts.is_empty() {
+        sections.push(format!(
+            "Changed parts:\n{}",
+            changed_parts
+                .iter()
+                .map(|((name, id), paths)| format!(
+                    "- {name} ({id}): {}",
+                    paths.iter().join(", ")
+                ))
+                .join("\n")
+        ));
+    }
+    if !summary.images.is_empty() {
+        sections.push(format!(
+            "Updated thumbnails:\n{}",
+            summary
+                .images
+                .iter()
+                .map(|p| format!("- {}", relative_path(root, p)))
+                .join("\n")
+        ));
+    }
+    if !summary.deleted.is_empty() {
+        sections.push(format!(
+            "Removed files:\n{}",
+            summary
+                .deleted
+                .iter()
+                .map(|p| format!("- {}", relative_path(root, p)))
+                .join("\n")
+        ));
+    }
+
+    let version = match state.version_name {
+        Some(ref name) => format!(", version {name}"),
+        None => "".into(),
+    };
+    formatdoc! {"
+        Pull {name} from OnShape
+
+        Document {document_id}, workspace {workspace_id}{version}, microversion {microversion}
+
+        {sections}
+        ",
+        name = document.name,
+        document_id = document.id,
+        sections = sections.join("\n\n"),
+    }
+}
+
+async fn git<I, S>(dir: &Utf8Path, args: I) -> Result<Output>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<std::ffi::OsStr>,
+{
+    let mut command = Command::new("git");
+    if !dir.as_str().is_empty() {
+        command.current_dir(dir);
+    }
+    command
+        .args(args)
+        .output()
+        .await
+        .context("Could not run git. Is it installed?")
+}
+
+fn check(subcommand: &str, output: Output) -> Result<()> {
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(git_error(subcommand, &output))
+    }
+}
+
+fn git_error(subcommand: &str, output: &Output) -> anyhow::Error {
+    anyhow!(
+        "git {subcommand} failed: {}",
+        String::from_utf8_lossy(&output.stderr).trim()
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use indoc::indoc;
+
+    use super::*;
+    use crate::{onshape::models::ExportFileFormat, state::FileRecord, test_fixtures};
+
+    #[test]
+    fn test_commit_message() {
+        let document = test_fixtures::document();
+        let root = Utf8Path::new("/repo");
+        let record = |format| FileRecord {
+            studio_id: "59628be740b2cf41a42e4c8a".into(),
+            part_id: "JHD".into(),
+            part_name: "Left Bracket".into(),
+            format,
+            microversion_id: "mv2".into(),
+        };
+        let state = PullState {
+            document_id: document.id.clone(),
+            workspace_id: document.default_workspace.id.clone(),
+            files: [
+                ("stl/left_bracket.stl".into(), record(ExportFileFormat::Stl)),
+                (
+                    "3mf/left_bracket.3mf".into(),
+                    record(ExportFileFormat::ThreeMF),
+                ),
+            ]
+            .into(),
+            ..Default::default()
+        };
+        let summary = PullSummary {
+            written: vec![
+                "/repo/stl/left_bracket.stl".into(),
+                "/repo/3mf/left_bracket.3mf".into(),
+            ],
+            images: vec!["/repo/images/left_bracket-300x300.png".into()],
+            deleted: vec!["/repo/stl/knob.stl".into()],
+            failed: vec![],
+        };
+
+        assert_eq!(
+            commit_message(
+                &document,
+                "da2053ce82593768f41daab4",
+                "b9f3c2d1e0a4f5b6c7d8e9f0",
+                &state,
+                root,
+                &summary
+            ),
+            indoc! {"
+                Pull Desk Organizer from OnShape
+
+                Document 6a1de9567f16cd7d1f564dbb, workspace da2053ce82593768f41daab4, microversion b9f3c2d1e0a4f5b6c7d8e9f0
+
+                Changed parts:
+                - Left Bracket (JHD): stl/left_bracket.stl, 3mf/left_bracket.3mf
+
+                Updated thumbnails:
+                - images/left_bracket-300x300.png
+
+                Removed files:
+                - stl/knob.stl
+            "}
+        );
+    }
+}
@@ -0,0 +1,37 @@
+use anyhow::{anyhow, Context, Result};
+use camino::Utf8Path;
+use tokio::process::Command;
+
+/// Runs `command` in `dir` using the platform's shell, with `env` added to its environment.
+/// Fails if the command can't be started, or exits unsuccessfully.
+pub(crate) async fn run_hook(
+    command: &str,
+    dir: &Utf8Path,
+    env: &[(&str, &str)],
+) -> Result<()> {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    // An empty path (for a config that wasn't loaded from a file) means the working
+    // directory
+    if !dir.as_str().is_empty() {
+        shell.current_dir(dir);
+    }
+    log::debug!("Running `{command}`");
+    let status = shell
+        .arg(command)
+        .envs(env.iter().copied())
+        .status()
+        .await
+        .with_context(|| format!("Could not run `{command}`"))?;
+    if !status.success() {
+        return Err(anyhow!("`{command}` failed ({status})"));
+    }
+    Ok(())
+}
@@ -0,0 +1,222 @@
+use std::fs;
+
+use anyhow::{anyhow, Result};
+use camino::Utf8Path;
+use clap::Args;
+use dialoguer::{theme::ColorfulTheme, Confirm, Input, MultiSelect};
+use indoc::formatdoc;
+
+use crate::{
+    onshape::{
+        environment_client,
+        models::{Document, DocumentElement, ExportFileFormat, TabElementType},
+        DocumentUrl, WorkspaceId,
+    },
+    GlobalOptions,
+};
+
+#[derive(Args, Debug)]
+pub struct InitOptions {
+    /// The URL of the OnShape document to sync, as shown in the browser's address bar. If
+    /// omitted, you will be prompted for it.
+    pub document_url: Option<String>,
+    /// Overwrite the config file if it already exists
+    #[arg(long)]
+    pub force: bool,
+}
+
+/// Interactively creates a config file describing which of a document's part studios to
+/// sync, and where each format is written
+pub async fn init(
+    config_path: &Utf8Path,
+    global_options: GlobalOptions,
+    options: InitOptions,
+) -> Result<()> {
+    if config_path.exists() && !options.force {
+        return Err(anyhow!(
+            "{config_path} already exists. Pass --force to overwrite it."
+        ));
+    }
+
+    let theme = ColorfulTheme::default();
+    let document_url = match options.document_url {
+        Some(url) => url,
+        None => Input::with_theme(&theme)
+            .with_prompt("OnShape document URL")
+            .validate_with(|s: &String| s.parse::<DocumentUrl>().map(|_| ()))
+            .interact_text()?,
+    };
+    let parsed_url: DocumentUrl = document_url.parse()?;
+    if parsed_url.version_id.is_some() {
+        return Err(anyhow!(
+            "offshape pulls from a workspace, but {document_url} refers to a version. Open \
+             the document's workspace, and use that URL instead."
+        ));
+    }
+
+    let client = environment_client(&global_options, None)?;
+    let document = client.get_document(&parsed_url.document_id).await?;
+    let workspace_id = parsed_url
+        .workspace_id
+        .clone()
+        .unwrap_or(document.default_workspace.id.clone());
+
+    let mut part_studios: Vec<DocumentElement> = client
+        .get_document_elements(&document.id, &workspace_id)
+        .await?
+        .into_values()
+        .filter(|e| e.element_type == TabElementType::PartStudio)
+        .collect();
+    part_studios.sort_by(|a, b| a.name.cmp(&b.name));
+    if part_studios.is_empty() {
+        return Err(anyhow!("{} has no part studios", document.name));
+    }
+
+    // Preselect the studio the URL pointed at, or every studio if it didn't point at one
+    let defaults: Vec<bool> = part_studios
+        .iter()
+        .map(|e| parsed_url.element_id.as_ref().is_none_or(|id| *id == e.id))
+        .collect();
+    let selected: Vec<DocumentElement> = MultiSelect::with_theme(&theme)
+        .with_prompt("Part studios to sync (space to toggle, enter to confirm)")
+        .items(&part_studios.iter().map(|e| &e.name).collect::<Vec<_>>())
+        .defaults(&defaults)
+        .interact()?
+        .into_iter()
+        .map(|i| part_studios[i].clone())
+        .collect();
+    if selected.is_empty() {
+        return Err(anyhow!("At least one part studio must be selected"));
+    }
+
+    let mut format_paths = vec![];
+    for format in ExportFileFormat::iter() {
+        let ext = format.extension();
+        if Confirm::with_theme(&theme)
+            .with_prompt(format!("Export .{ext} files?"))
+            .default(true)
+            .interact()?
+        {
+            let path: String = Input::with_theme(&theme)
+                .with_prompt(format!("Directory for .{ext} files"))
+                .default(format!("export/{ext}/"))
+                .interact_text()?;
+            format_paths.push((*format, path));
+        }
+    }
+
+    fs::write(
+        config_path,
+        render_config(
+            &document_url,
+            &document,
+            &workspace_id,
+            &format_paths,
+            &selected,
+        ),
+    )?;
+    log::info!("Wrote {config_path}");
+
+    Ok(())
+}
+
+fn render_config(
+    document_url: &str,
+    document: &Document,
+    workspace_id: &WorkspaceId,
+    format_paths: &[(ExportFileFormat, String)],
+    part_studios: &[DocumentElement],
+) -> String {
+    let quote = |s: &str| toml::Value::String(s.into()).to_string();
+
+    let format_paths: String = format_paths
+        .iter()
+        .map(|(format, path)| {
+            let key = match format {
+                ExportFileFormat::ThreeMF => "3mf_path",
+                ExportFileFormat::Step => "step_path",
+                ExportFileFormat::Stl => "stl_path",
+            };
+            format!("{key} = {}\n", quote(path))
+        })
+        .collect();
+    let part_studios: String = part_studios
+        .iter()
+        .map(|studio| {
+            formatdoc! {"
+
+                [[part_studio]]
+                display_name = {name}
+                id = {id}
+                ",
+                name = quote(&studio.name),
+                id = quote(&studio.id),
+            }
+        })
+        .collect();
+
+    formatdoc! {"
+        # Generated by `offshape init` from
+        # {document_url}
+
+        # Directories that parts are exported into, relative to this file. Remove a line to
+        # stop exporting that format.
+        {format_paths}
+        # {document_name}
+        [document]
+        id = {document_id}
+        workspace_id = {workspace_id}
+        {part_studios}",
+        document_name = document.name,
+        document_id = quote(&document.id),
+        workspace_id = quote(workspace_id),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use indoc::indoc;
+
+    use super::*;
+    use crate::test_fixtures;
+
+    #[test]
+    fn test_render_config() {
+        let document = test_fixtures::document();
+        let studio = DocumentElement {
+            id: "59628be740b2cf41a42e4c8a".into(),
+            name: "Printables".into(),
+            file_name: None,
+            element_type: TabElementType::PartStudio,
+        };
+
+        let rendered = render_config(
+            "https://cad.onshape.com/documents/6a1de9567f16cd7d1f564dbb",
+            &document,
+            &document.default_workspace.id,
+            &[(ExportFileFormat::Stl, "export/stl/".into())],
+            &[studio],
+        );
+        assert_eq!(
+            rendered,
+            indoc! {r#"
+                # Generated by `offshape init` from
+                # https://cad.onshape.com/documents/6a1de9567f16cd7d1f564dbb
+
+                # Directories that parts are exported into, relative to this file. Remove a line to
+                # stop exporting that format.
+                stl_path = "export/stl/"
+
+                # Desk Organizer
+                [document]
+                id = "6a1de9567f16cd7d1f564dbb"
+                workspace_id = "da2053ce82593768f41daab4"
+
+                [[part_studio]]
+                display_name = "Printables"
+                id = "59628be740b2cf41a42e4c8a"
+            "#}
+        );
+        assert!(toml::from_str::<crate::SyncConfig>(&rendered).is_ok());
+    }
+}
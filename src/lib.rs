@@ -1,28 +1,80 @@
-#![feature(let_chains)]
-#![feature(file_set_times)]
-
+mod check;
 mod config;
-#[allow(dead_code)]
-mod onshape;
+mod git;
+mod hooks;
+mod init;
+mod list;
+pub mod onshape;
 mod pull;
+mod release;
+mod report;
+mod schema;
 mod show;
-
-use std::fs;
+mod state;
+mod status;
+mod table;
+#[cfg(test)]
+mod test_fixtures;
+mod watch;
 
 use anyhow::Result;
 use camino::{Utf8Path, Utf8PathBuf};
 
-use crate::config::SyncConfig;
+use crate::config::{
+    interpolate_env, merge_config_files, read_config_files, rebase_paths, ConfigFile,
+};
 pub use crate::{
-    config::GlobalOptions,
-    pull::{export, PullOptions},
-    show::{show_parts, OutputFormat, ShowPartsOptions},
+    check::{check, CheckOptions},
+    config::{
+        find_config, GlobalOptions, Hooks, SyncConfig, SyncedDocument, SyncedPartStudio,
+        Thumbnails, CONFIG_FILE_NAME,
+    },
+    init::{init, InitOptions},
+    list::{
+        list_documents, list_versions, list_workspaces, ListDocumentContentsOptions,
+        ListDocumentsOptions,
+    },
+    pull::{
+        execute_plan, export, plan_pull, pull, PlannedAction, PullOptions, PullPlan,
+        PullSummary,
+    },
+    release::{release, ReleaseOptions},
+    report::{
+        JsonReporter, LogReporter, NullReporter, ProgressBarReporter, PullEvent,
+        PullReporter, ReporterKind,
+    },
+    schema::schema,
+    show::{
+        show_elements, show_parts, visit_synced_parts, OutputFormat, ShowElementsOptions,
+        ShowPartsOptions,
+    },
+    state::{FileRecord, PullState, STATE_FILE_NAME},
+    status::{local_status, status, Status, StatusEntry, StatusKind, StatusOptions},
+    watch::{watch, WatchOptions},
 };
 
 pub fn load_config(config_path: &Utf8Path) -> Result<SyncConfig> {
     let config_path = config_path.canonicalize_utf8()?;
     let config_dir: Utf8PathBuf = config_path.parent().unwrap().into();
-    let mut config: SyncConfig = toml::from_str(&fs::read_to_string(config_path)?)?;
+    parse_config(&read_config_files(&config_path)?, config_dir)
+}
+
+/// Parses a config from the files making it up, resolving its paths against `config_dir`
+pub(crate) fn parse_config(
+    files: &[ConfigFile],
+    config_dir: Utf8PathBuf,
+) -> Result<SyncConfig> {
+    // Variables are expanded before paths are rebased onto the including file's directory,
+    // so that a variable may hold an absolute path
+    let mut files = files.to_vec();
+    for file in files.iter_mut() {
+        interpolate_env(&mut file.table)?;
+    }
+    rebase_paths(&mut files);
+    let table = merge_config_files(&files);
+    let mut config: SyncConfig = toml::Value::Table(table).try_into()?;
+    config.resolve_urls()?;
+    config.root = config_dir.clone();
 
     if let Some(three_mf_path) = config.three_mf_path {
         config.three_mf_path = Some({
@@ -48,5 +100,20 @@ pub fn load_config(config_path: &Utf8Path) -> Result<SyncConfig> {
         });
     }
 
+    for studio in config.part_studios.iter_mut() {
+        let paths = [
+            &mut studio.three_mf_path,
+            &mut studio.step_path,
+            &mut studio.stl_path,
+        ];
+        for path in paths.into_iter().flatten() {
+            *path = config_dir.join(&**path).into();
+        }
+    }
+    if let Some(ref mut thumbnails) = config.thumbnails {
+        thumbnails.path = config_dir.join(&*thumbnails.path).into();
+    }
+    config.check_studio_formats()?;
+
     Ok(config)
 }
@@ -0,0 +1,125 @@
+use anyhow::Result;
+use clap::Args;
+use serde::Serialize;
+
+use crate::{
+    onshape::{environment_client, DocumentId, DocumentUrl},
+    table::Table,
+    GlobalOptions, OutputFormat,
+};
+
+#[derive(Args, Debug)]
+pub struct ListDocumentsOptions {
+    /// Only lists documents matching this search
+    #[arg(long, short)]
+    pub search: Option<String>,
+    /// The maximum number of documents to list
+    #[arg(long, default_value_t = 20)]
+    pub limit: usize,
+    #[clap(long, short, default_value_t = OutputFormat::default())]
+    pub format: OutputFormat,
+}
+
+#[derive(Args, Debug)]
+pub struct ListDocumentContentsOptions {
+    /// The document's ID, or URL
+    #[arg(value_parser = DocumentUrl::parse_document_id)]
+    pub document: DocumentId,
+    #[clap(long, short, default_value_t = OutputFormat::default())]
+    pub format: OutputFormat,
+}
+
+/// Prints the documents visible to the user, most recently modified first
+pub async fn list_documents(
+    global_options: GlobalOptions,
+    options: ListDocumentsOptions,
+) -> Result<()> {
+    let client = environment_client(&global_options, None)?;
+    let documents = client
+        .list_documents(options.search.as_deref(), options.limit)
+        .await?;
+
+    print_list(
+        options.format,
+        "documents",
+        &documents,
+        vec!["id", "name", "default_workspace_id"],
+        |d| {
+            vec![
+                d.id.to_string(),
+                d.name.clone(),
+                d.default_workspace.id.to_string(),
+            ]
+        },
+    )
+}
+
+/// Prints a document's versions
+pub async fn list_versions(
+    global_options: GlobalOptions,
+    options: ListDocumentContentsOptions,
+) -> Result<()> {
+    let client = environment_client(&global_options, None)?;
+    let versions = client.get_document_versions(&options.document).await?;
+
+    print_list(
+        options.format,
+        "versions",
+        &versions,
+        vec!["id", "name", "description"],
+        |v| {
+            vec![
+                v.id.to_string(),
+                v.name.clone(),
+                v.description.clone().unwrap_or_default(),
+            ]
+        },
+    )
+}
+
+/// Prints a document's workspaces
+pub async fn list_workspaces(
+    global_options: GlobalOptions,
+    options: ListDocumentContentsOptions,
+) -> Result<()> {
+    let client = environment_client(&global_options, None)?;
+    let workspaces = client.get_document_workspaces(&options.document).await?;
+
+    print_list(
+        options.format,
+        "workspaces",
+        &workspaces,
+        vec!["id", "name"],
+        |w| vec![w.id.to_string(), w.name.clone()],
+    )
+}
+
+/// Prints `items` in `format`. Friendly output is a table without a header.
+fn print_list<T: Serialize>(
+    format: OutputFormat,
+    noun: &str,
+    items: &[T],
+    columns: Vec<&'static str>,
+    row: impl Fn(&T) -> Vec<String>,
+) -> Result<()> {
+    if format == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(items)?);
+        return Ok(());
+    }
+    if format == OutputFormat::Friendly && items.is_empty() {
+        println!("No {noun} found");
+        return Ok(());
+    }
+
+    let mut table = Table::new(columns);
+    for item in items {
+        table.push(row(item));
+    }
+    let rendered = table.render(format);
+    match format {
+        // Skip the header
+        OutputFormat::Friendly => print!("{}", rendered.split_once('\n').unwrap().1),
+        _ => print!("{rendered}"),
+    }
+    Ok(())
+}
@@ -0,0 +1,226 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use bytes::Bytes;
+use tokio::runtime::{self, Runtime};
+
+use super::{
+    client,
+    models::{
+        Document, DocumentElement, DocumentId, ElementId, ExportFileFormat, Part, PartId,
+        ThumbnailSize, TranslationJobWithOutput, Version, Workspace, WorkspaceId, Wvm,
+    },
+};
+
+/// A blocking OnShape API client, implemented as a thin wrapper around the asynchronous
+/// [`client::OnShapeClient`].
+///
+/// Construct one with [`client::OnShapeClientBuilder::build_blocking`], or wrap an existing
+/// client with [`OnShapeClient::new`]. Each call drives the underlying future to completion
+/// on a private runtime, and so must not be made from within an async context.
+pub struct OnShapeClient {
+    inner: client::OnShapeClient,
+    runtime: Runtime,
+}
+
+impl OnShapeClient {
+    /// Wraps `inner`, giving it a runtime of its own to run on
+    pub fn new(inner: client::OnShapeClient) -> Result<Self> {
+        Ok(Self {
+            inner,
+            runtime: runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()?,
+        })
+    }
+
+    /// The asynchronous client this wrapper delegates to
+    pub fn inner(&self) -> &client::OnShapeClient {
+        &self.inner
+    }
+
+    pub fn get_document(&self, document_id: &DocumentId) -> Result<Document> {
+        self.runtime.block_on(self.inner.get_document(document_id))
+    }
+
+    pub fn list_documents(
+        &self,
+        search: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<Document>> {
+        self.runtime
+            .block_on(self.inner.list_documents(search, limit))
+    }
+
+    pub fn get_document_versions(&self, document_id: &DocumentId) -> Result<Vec<Version>> {
+        self.runtime
+            .block_on(self.inner.get_document_versions(document_id))
+    }
+
+    pub fn create_version(
+        &self,
+        document_id: &DocumentId,
+        workspace_id: &WorkspaceId,
+        name: &str,
+        description: Option<&str>,
+    ) -> Result<Version> {
+        self.runtime.block_on(self.inner.create_version(
+            document_id,
+            workspace_id,
+            name,
+            description,
+        ))
+    }
+
+    pub fn get_document_workspaces(
+        &self,
+        document_id: &DocumentId,
+    ) -> Result<Vec<Workspace>> {
+        self.runtime
+            .block_on(self.inner.get_document_workspaces(document_id))
+    }
+
+    pub fn get_document_elements(
+        &self,
+        document_id: &DocumentId,
+        wvm: impl Into<Wvm>,
+    ) -> Result<HashMap<ElementId, DocumentElement>> {
+        self.runtime
+            .block_on(self.inner.get_document_elements(document_id, wvm))
+    }
+
+    pub fn get_current_microversion(
+        &self,
+        document_id: &DocumentId,
+        workspace_id: &WorkspaceId,
+    ) -> Result<String> {
+        self.runtime.block_on(
+            self.inner
+                .get_current_microversion(document_id, workspace_id),
+        )
+    }
+
+    pub fn get_studio_parts(
+        &self,
+        document_id: &DocumentId,
+        wvm: impl Into<Wvm>,
+        part_studio_id: &ElementId,
+    ) -> Result<Vec<Part>> {
+        self.runtime.block_on(
+            self.inner
+                .get_studio_parts(document_id, wvm, part_studio_id),
+        )
+    }
+
+    pub fn get_studio_parts_json(
+        &self,
+        document_id: &DocumentId,
+        wvm: impl Into<Wvm>,
+        part_studio_id: &ElementId,
+    ) -> Result<String> {
+        self.runtime.block_on(self.inner.get_studio_parts_json(
+            document_id,
+            wvm,
+            part_studio_id,
+        ))
+    }
+
+    pub fn get_part_stl(
+        &self,
+        document_id: &DocumentId,
+        wvm: impl Into<Wvm>,
+        element_id: &ElementId,
+        part_id: &PartId,
+    ) -> Result<String> {
+        self.runtime.block_on(
+            self.inner
+                .get_part_stl(document_id, wvm, element_id, part_id),
+        )
+    }
+
+    pub fn get_element_thumbnail(
+        &self,
+        document_id: &DocumentId,
+        wvm: impl Into<Wvm>,
+        element_id: &ElementId,
+        size: &ThumbnailSize,
+    ) -> Result<Bytes> {
+        self.runtime.block_on(self.inner.get_element_thumbnail(
+            document_id,
+            wvm,
+            element_id,
+            size,
+        ))
+    }
+
+    pub fn get_part_shaded_view(
+        &self,
+        document_id: &DocumentId,
+        wvm: impl Into<Wvm>,
+        element_id: &ElementId,
+        part_id: &PartId,
+        size: &ThumbnailSize,
+    ) -> Result<Bytes> {
+        self.runtime.block_on(self.inner.get_part_shaded_view(
+            document_id,
+            wvm,
+            element_id,
+            part_id,
+            size,
+        ))
+    }
+
+    pub fn get_part_parasolid(
+        &self,
+        document_id: &DocumentId,
+        microversion_id: &str,
+        element_id: &ElementId,
+        part_id: &PartId,
+        configuration: &str,
+    ) -> Result<String> {
+        self.runtime.block_on(self.inner.get_part_parasolid(
+            document_id,
+            microversion_id,
+            element_id,
+            part_id,
+            configuration,
+        ))
+    }
+
+    pub fn begin_translation(
+        &self,
+        format: &ExportFileFormat,
+        document_id: &DocumentId,
+        wvm: impl Into<Wvm>,
+        element_id: &ElementId,
+        part_id: &PartId,
+        basename: &str,
+    ) -> Result<TranslationJobWithOutput> {
+        self.runtime.block_on(self.inner.begin_translation(
+            format,
+            document_id,
+            wvm,
+            element_id,
+            part_id,
+            basename,
+        ))
+    }
+
+    pub fn check_translation(
+        &self,
+        job: &TranslationJobWithOutput,
+    ) -> Result<TranslationJobWithOutput> {
+        self.runtime.block_on(self.inner.check_translation(job))
+    }
+
+    pub fn download_translated_file(
+        &self,
+        job: &TranslationJobWithOutput,
+        strip_indeterminism: bool,
+    ) -> Result<Bytes> {
+        self.runtime.block_on(
+            self.inner
+                .download_translated_file(job, strip_indeterminism),
+        )
+    }
+}
@@ -0,0 +1,240 @@
+use std::{collections::BTreeMap, fs, future::Future, sync::Mutex};
+
+use anyhow::{anyhow, Context, Result};
+use base64::Engine as _;
+use camino::{Utf8Path, Utf8PathBuf};
+use reqwest::{Request, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+
+use super::client::redacted_headers;
+
+/// Captures the requests made by `OnShapeClient` into a directory of JSON files (one per
+/// interaction), or serves previously captured responses back without touching the
+/// network.
+pub enum Cassette {
+    Record {
+        dir: Utf8PathBuf,
+        next_index: Mutex<usize>,
+    },
+    Replay {
+        dir: Utf8PathBuf,
+        interactions: Mutex<Vec<Option<Interaction>>>,
+    },
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Interaction {
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: BTreeMap<String, String>,
+    pub body: Option<String>,
+}
+impl RecordedRequest {
+    fn from_request(req: &Request) -> Self {
+        Self {
+            method: req.method().to_string(),
+            url: req.url().to_string(),
+            headers: redacted_headers(req.headers()),
+            body: req
+                .body()
+                .and_then(|b| b.as_bytes())
+                .map(|b| String::from_utf8_lossy(b).into_owned()),
+        }
+    }
+
+    fn matches(&self, other: &RecordedRequest) -> bool {
+        self.method == other.method && self.url == other.url && self.body == other.body
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RecordedResponse {
+    pub status: u16,
+    pub headers: BTreeMap<String, String>,
+    /// The response body, base64 encoded so that binary exports survive the round trip
+    pub body_base64: String,
+}
+
+impl Cassette {
+    pub fn record(dir: &Utf8Path) -> Result<Self> {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Could not create cassette directory {dir}"))?;
+        Ok(Cassette::Record {
+            dir: dir.into(),
+            next_index: Mutex::new(0),
+        })
+    }
+
+    pub fn replay(dir: &Utf8Path) -> Result<Self> {
+        let mut paths: Vec<Utf8PathBuf> = dir
+            .read_dir_utf8()
+            .with_context(|| format!("Could not read cassette directory {dir}"))?
+            .filter_map(|e| e.ok())
+            .map(|e| e.into_path())
+            .filter(|p| p.extension() == Some("json"))
+            .collect();
+        paths.sort();
+
+        let mut interactions = vec![];
+        for p in paths {
+            let interaction: Interaction = serde_json::from_str(&fs::read_to_string(&p)?)
+                .with_context(|| format!("Malformed cassette entry {p}"))?;
+            interactions.push(Some(interaction));
+        }
+
+        Ok(Cassette::Replay {
+            dir: dir.into(),
+            interactions: Mutex::new(interactions),
+        })
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        matches!(self, Cassette::Replay { .. })
+    }
+
+    /// Performs `req` according to the cassette's mode. When recording, `send` is used to
+    /// perform the request and its response is written to disk before being returned.
+    pub async fn execute<F, Fut>(&self, req: Request, send: F) -> Result<Response>
+    where
+        F: FnOnce(Request) -> Fut,
+        Fut: Future<Output = Result<Response>>,
+    {
+        let recorded_req = RecordedRequest::from_request(&req);
+        match self {
+            Cassette::Record { dir, next_index } => {
+                let res = send(req).await?;
+                let status = res.status();
+                let headers = res.headers().clone();
+                let body = res.bytes().await?;
+
+                let interaction = Interaction {
+                    request: recorded_req,
+                    response: RecordedResponse {
+                        status: status.as_u16(),
+                        headers: redacted_headers(&headers),
+                        body_base64: base64::engine::general_purpose::STANDARD.encode(&body),
+                    },
+                };
+                let path = {
+                    let mut i = next_index.lock().unwrap();
+                    let path = dir.join(format!(
+                        "{index:04}-{method}.json",
+                        index = *i,
+                        method = interaction.request.method.to_lowercase()
+                    ));
+                    *i += 1;
+                    path
+                };
+                fs::write(&path, serde_json::to_string_pretty(&interaction)?)?;
+
+                interaction.response.into_response()
+            }
+            Cassette::Replay { dir, interactions } => {
+                let mut interactions = interactions.lock().unwrap();
+                let slot = interactions
+                    .iter_mut()
+                    .find(|i| i.as_ref().is_some_and(|i| i.request.matches(&recorded_req)))
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "No recorded response in {dir} for {} {}",
+                            recorded_req.method,
+                            recorded_req.url
+                        )
+                    })?;
+                slot.take().unwrap().response.into_response()
+            }
+        }
+    }
+}
+
+impl RecordedResponse {
+    fn into_response(self) -> Result<Response> {
+        let mut builder =
+            http::Response::builder().status(StatusCode::from_u16(self.status)?);
+        for (name, value) in self.headers.iter() {
+            builder = builder.header(name, value);
+        }
+        let body = base64::engine::general_purpose::STANDARD.decode(self.body_base64)?;
+        Ok(builder.body(body)?.into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use http::header;
+    use reqwest::Client;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_replay_matches_in_recorded_order() {
+        let interaction = |body: &str| {
+            Some(Interaction {
+                request: RecordedRequest {
+                    method: "GET".into(),
+                    url: "https://cad.onshape.com/api/translations/abc".into(),
+                    headers: BTreeMap::new(),
+                    body: None,
+                },
+                response: RecordedResponse {
+                    status: 200,
+                    headers: BTreeMap::new(),
+                    body_base64: base64::engine::general_purpose::STANDARD.encode(body),
+                },
+            })
+        };
+        let cassette = Cassette::Replay {
+            dir: "cassette".into(),
+            interactions: Mutex::new(vec![interaction("ACTIVE"), interaction("DONE")]),
+        };
+
+        let client = Client::new();
+        let send = |_: Request| async { panic!("Replay must not send") };
+        let request = || {
+            client
+                .get("https://cad.onshape.com/api/translations/abc")
+                .header(header::AUTHORIZATION, "On key:HmacSHA256:secret")
+                .build()
+                .unwrap()
+        };
+
+        assert_eq!(
+            cassette
+                .execute(request(), send)
+                .await
+                .unwrap()
+                .text()
+                .await
+                .unwrap(),
+            "ACTIVE"
+        );
+        assert_eq!(
+            cassette
+                .execute(request(), send)
+                .await
+                .unwrap()
+                .text()
+                .await
+                .unwrap(),
+            "DONE"
+        );
+        assert!(cassette.execute(request(), send).await.is_err());
+    }
+
+    #[test]
+    fn test_authorization_is_redacted() {
+        let req = Client::new()
+            .get("https://cad.onshape.com/api/documents")
+            .header(header::AUTHORIZATION, "On key:HmacSHA256:secret")
+            .build()
+            .unwrap();
+        let recorded = RecordedRequest::from_request(&req);
+        assert_eq!(recorded.headers["authorization"], "<redacted>");
+    }
+}
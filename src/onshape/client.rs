@@ -1,77 +1,258 @@
-use std::{collections::HashMap, str::FromStr, time::SystemTime};
+use std::{
+    collections::{BTreeMap, HashMap},
+    env,
+    str::FromStr,
+    time::{Duration, Instant, SystemTime},
+};
 
 use anyhow::{anyhow, Result};
 use base64::Engine as _;
 use bytes::Bytes;
 use camino::Utf8PathBuf;
-use governor::{
-    clock::{Clock, QuantaClock},
-    DefaultDirectRateLimiter, Quota, RateLimiter,
-};
+use dotenv::dotenv;
+use governor::{DefaultDirectRateLimiter, Quota, RateLimiter};
 use hmac::{Hmac, Mac};
-use http::header;
+use http::{header, HeaderMap, HeaderValue, StatusCode};
 use lazy_static::lazy_static;
 use nonzero_ext::nonzero;
 use rand::{distributions::Alphanumeric, thread_rng, Rng};
 use regex::Regex;
 use reqwest::{
-    blocking::{ClientBuilder, RequestBuilder, Response},
-    redirect::Policy,
-    IntoUrl, Method, Proxy, Url,
+    redirect::Policy, ClientBuilder, IntoUrl, Method, Proxy, Request, RequestBuilder,
+    Response, Url,
 };
 use sha2::Sha256;
-
-use super::models::{
-    DocumentElement, ExportFileFormat, Part, TranslationJobWithOutput, TranslationRequest,
-    TranslationState, TranslationUnit,
+use tokio::time::sleep;
+
+use super::{
+    blocking,
+    cassette::Cassette,
+    models::{
+        Document, DocumentElement, DocumentId, DocumentPage, ElementId, ExportFileFormat,
+        Microversion, Part, PartId, ShadedViews, ThumbnailSize, TranslationJobWithOutput,
+        TranslationRequest, TranslationState, TranslationUnit, Version, VersionRequest,
+        Workspace, WorkspaceId, Wvm,
+    },
 };
 use crate::onshape::models::{TranslationJob, TranslationResolution};
 
-const BASE_URL: &str = "https://cad.onshape.com/api";
+pub const DEFAULT_BASE_URL: &str = "https://cad.onshape.com/api";
+
+/// How many times a request is retried after OnShape responds that we are rate limited
+const MAX_RETRIES: u32 = 3;
+/// The delay before the first retry, when OnShape doesn't say how long to wait. Each retry
+/// doubles it.
+const RETRY_BACKOFF: Duration = Duration::from_secs(1);
+
+pub(crate) const REDACTED_VALUE: &str = "<redacted>";
 
 type HmacSha256 = Hmac<Sha256>;
 
+/// An asynchronous OnShape API client. See [`crate::onshape::blocking::OnShapeClient`] for a
+/// blocking equivalent.
 pub struct OnShapeClient {
-    pub http_client: reqwest::blocking::Client,
+    pub http_client: reqwest::Client,
+    base_url: String,
     rate_limiter: DefaultDirectRateLimiter,
     access_key: String,
     secret_key: String,
+    cassette: Option<Cassette>,
 }
 
-impl OnShapeClient {
-    pub fn new(
-        access_key: String,
-        secret_key: String,
-        proxy_url: Option<Url>,
-    ) -> Result<Self> {
-        Ok(Self {
+/// Configures and constructs an [`OnShapeClient`] (or its blocking equivalent).
+#[derive(Default)]
+pub struct OnShapeClientBuilder {
+    access_key: Option<String>,
+    secret_key: Option<String>,
+    base_url: Option<String>,
+    proxy_url: Option<Url>,
+    cassette: Option<Cassette>,
+}
+
+impl OnShapeClientBuilder {
+    /// Reads API credentials from the `ONSHAPE_ACCESS_KEY` and `ONSHAPE_SECRET_KEY`
+    /// environment variables, loading a `.env` file first if one is present.
+    pub fn credentials_from_env(mut self) -> Result<Self> {
+        dotenv().ok();
+        self.access_key = Some(env::var("ONSHAPE_ACCESS_KEY")?);
+        self.secret_key = Some(env::var("ONSHAPE_SECRET_KEY")?);
+        Ok(self)
+    }
+
+    pub fn credentials(mut self, access_key: String, secret_key: String) -> Self {
+        self.access_key = Some(access_key);
+        self.secret_key = Some(secret_key);
+        self
+    }
+
+    /// The root of the OnShape REST API. Defaults to [`DEFAULT_BASE_URL`].
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = Some(base_url.into());
+        self
+    }
+
+    /// Routes all requests through an HTTP proxy. Certificate validation is disabled so
+    /// that intercepting proxies can be used for debugging.
+    pub fn proxy_url(mut self, proxy_url: Option<Url>) -> Self {
+        self.proxy_url = proxy_url;
+        self
+    }
+
+    pub fn cassette(mut self, cassette: Option<Cassette>) -> Self {
+        self.cassette = cassette;
+        self
+    }
+
+    pub fn build(self) -> Result<OnShapeClient> {
+        let replaying = self.cassette.as_ref().is_some_and(|c| c.is_replaying());
+        let (access_key, secret_key) = match (self.access_key, self.secret_key) {
+            (Some(access_key), Some(secret_key)) => (access_key, secret_key),
+            // Replayed sessions never reach the network, so credentials are optional
+            _ if replaying => Default::default(),
+            _ => return Err(anyhow!("OnShape API credentials were not provided")),
+        };
+
+        Ok(OnShapeClient {
             http_client: {
                 let mut b = ClientBuilder::new().gzip(true).redirect(Policy::none());
-                if let Some(proxy_url) = proxy_url {
+                if let Some(proxy_url) = self.proxy_url {
                     b = b
                         .proxy(Proxy::all(proxy_url)?)
                         .danger_accept_invalid_certs(true);
                 }
                 b.build()?
             },
+            base_url: self
+                .base_url
+                .unwrap_or(DEFAULT_BASE_URL.into())
+                .trim_end_matches('/')
+                .into(),
             rate_limiter: RateLimiter::direct(Quota::per_second(nonzero!(4u32))),
-            access_key: access_key,
-            secret_key: secret_key,
+            access_key,
+            secret_key,
+            cassette: self.cassette,
         })
     }
 
-    pub fn get_document_elements(
+    pub fn build_blocking(self) -> Result<blocking::OnShapeClient> {
+        blocking::OnShapeClient::new(self.build()?)
+    }
+}
+
+impl OnShapeClient {
+    pub fn builder() -> OnShapeClientBuilder {
+        OnShapeClientBuilder::default()
+    }
+
+    pub async fn get_document(&self, document_id: &DocumentId) -> Result<Document> {
+        let url = format!("{}/documents/{document_id}", self.base_url);
+        Ok(self
+            .send(self.request(Method::GET, url))
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+
+    /// Returns up to `limit` of the documents visible to the user, most recently modified
+    /// first. If `search` is given, only documents matching it are returned.
+    pub async fn list_documents(
+        &self,
+        search: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<Document>> {
+        let mut url = Url::from_str(&format!("{}/documents", self.base_url))?;
+        {
+            let mut query = url.query_pairs_mut();
+            query.append_pair("sortColumn", "modifiedAt");
+            query.append_pair("sortOrder", "desc");
+            if let Some(search) = search {
+                query.append_pair("q", search);
+            }
+        }
+
+        let mut documents = vec![];
+        let mut next = Some(url);
+        while let Some(url) = next.take() {
+            let page: DocumentPage = self
+                .send(self.request(Method::GET, url))
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+            documents.extend(page.items);
+            if documents.len() < limit {
+                next = page.next;
+            }
+        }
+        documents.truncate(limit);
+        Ok(documents)
+    }
+
+    pub async fn get_document_versions(
+        &self,
+        document_id: &DocumentId,
+    ) -> Result<Vec<Version>> {
+        let url = format!("{}/documents/d/{document_id}/versions", self.base_url);
+        Ok(self
+            .send(self.request(Method::GET, url))
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+
+    /// Creates a named version of the workspace's current state
+    pub async fn create_version(
+        &self,
+        document_id: &DocumentId,
+        workspace_id: &WorkspaceId,
+        name: &str,
+        description: Option<&str>,
+    ) -> Result<Version> {
+        let url = format!("{}/documents/d/{document_id}/versions", self.base_url);
+        let payload = VersionRequest {
+            document_id: document_id.clone(),
+            workspace_id: workspace_id.clone(),
+            name: name.into(),
+            description: description.map(Into::into),
+        };
+        Ok(self
+            .send(self.request(Method::POST, url).json(&payload))
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+
+    pub async fn get_document_workspaces(
+        &self,
+        document_id: &DocumentId,
+    ) -> Result<Vec<Workspace>> {
+        let url = format!("{}/documents/d/{document_id}/workspaces", self.base_url);
+        Ok(self
+            .send(self.request(Method::GET, url))
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+
+    pub async fn get_document_elements(
         &self,
-        document_id: &String,
-        workspace_id: &String,
-    ) -> Result<HashMap<String, DocumentElement>> {
+        document_id: &DocumentId,
+        wvm: impl Into<Wvm>,
+    ) -> Result<HashMap<ElementId, DocumentElement>> {
         let url = format!(
-            "{}/documents/d/{document_id}/w/{workspace_id}/elements",
-            BASE_URL,
-            document_id = document_id,
-            workspace_id = workspace_id
+            "{}/documents/d/{document_id}/{wvm}/elements",
+            self.base_url,
+            wvm = wvm.into(),
         );
-        let elements: Vec<DocumentElement> = self.request(Method::GET, url).send()?.json()?;
+        let elements: Vec<DocumentElement> = self
+            .send(self.request(Method::GET, url))
+            .await?
+            .json()
+            .await?;
 
         let mut elements_by_id = HashMap::new();
         for e in elements {
@@ -80,57 +261,79 @@ impl OnShapeClient {
         Ok(elements_by_id)
     }
 
-    pub fn get_studio_parts(
+    /// Returns the workspace's current microversion, which changes whenever anything in the
+    /// workspace is edited
+    pub async fn get_current_microversion(
         &self,
-        document_id: &String,
-        workspace_id: &String,
-        part_studio_id: &String,
+        document_id: &DocumentId,
+        workspace_id: &WorkspaceId,
+    ) -> Result<String> {
+        let url = format!(
+            "{}/documents/d/{document_id}/w/{workspace_id}/currentmicroversion",
+            self.base_url,
+        );
+        let microversion: Microversion = self
+            .send(self.request(Method::GET, url))
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(microversion.microversion)
+    }
+
+    pub async fn get_studio_parts(
+        &self,
+        document_id: &DocumentId,
+        wvm: impl Into<Wvm>,
+        part_studio_id: &ElementId,
     ) -> Result<Vec<Part>> {
         Ok(self
-            .get_studio_parts_internal(document_id, workspace_id, part_studio_id)?
-            .json()?)
+            .get_studio_parts_internal(document_id, wvm, part_studio_id)
+            .await?
+            .json()
+            .await?)
     }
 
-    pub fn get_studio_parts_json(
+    pub async fn get_studio_parts_json(
         &self,
-        document_id: &String,
-        workspace_id: &String,
-        part_studio_id: &String,
+        document_id: &DocumentId,
+        wvm: impl Into<Wvm>,
+        part_studio_id: &ElementId,
     ) -> Result<String> {
         Ok(self
-            .get_studio_parts_internal(document_id, workspace_id, part_studio_id)?
-            .text()?)
+            .get_studio_parts_internal(document_id, wvm, part_studio_id)
+            .await?
+            .text()
+            .await?)
     }
 
-    fn get_studio_parts_internal(
+    async fn get_studio_parts_internal(
         &self,
-        document_id: &String,
-        workspace_id: &String,
-        part_studio_id: &String,
+        document_id: &DocumentId,
+        wvm: impl Into<Wvm>,
+        part_studio_id: &ElementId,
     ) -> Result<Response> {
         let url = format!(
-            "{}/parts/d/{document_id}/w/{workspace_id}/e/{part_studio_id}",
-            BASE_URL,
+            "{}/parts/d/{document_id}/{wvm}/e/{part_studio_id}",
+            self.base_url,
+            wvm = wvm.into(),
         );
 
-        let res = self.request(Method::GET, url).send()?;
+        let res = self.send(self.request(Method::GET, url)).await?;
         Ok(res)
     }
 
-    pub fn get_part_stl(
+    pub async fn get_part_stl(
         &self,
-        document_id: &String,
-        workspace_id: &String,
-        element_id: &String,
-        part_id: &String,
+        document_id: &DocumentId,
+        wvm: impl Into<Wvm>,
+        element_id: &ElementId,
+        part_id: &PartId,
     ) -> Result<String> {
         let mut url = Url::from_str(&format!(
-            "{}/parts/d/{document_id}/w/{microversion_id}/e/{element_id}/partid/{part_id}/stl?",
-            BASE_URL,
-            document_id = document_id,
-            microversion_id = workspace_id,
-            element_id = element_id,
-            part_id = part_id,
+            "{}/parts/d/{document_id}/{wvm}/e/{element_id}/partid/{part_id}/stl?",
+            self.base_url,
+            wvm = wvm.into(),
         ))?;
         {
             let mut query = url.query_pairs_mut();
@@ -142,7 +345,7 @@ impl OnShapeClient {
             query.append_pair("configuration", "");
         }
 
-        let res = self.request(Method::GET, url).send()?;
+        let res = self.send(self.request(Method::GET, url)).await?;
         assert!(res.status().is_redirection(), "Redirect expected");
 
         let redirect_url = res
@@ -150,20 +353,91 @@ impl OnShapeClient {
             .get("location")
             .expect("Missing location header")
             .to_str()?;
-        Ok(self.request(Method::GET, redirect_url).send()?.text()?)
+        Ok(self
+            .send(self.request(Method::GET, redirect_url))
+            .await?
+            .text()
+            .await?)
+    }
+
+    /// Renders a tab of the document, like a part studio, as a PNG of the given size
+    pub async fn get_element_thumbnail(
+        &self,
+        document_id: &DocumentId,
+        wvm: impl Into<Wvm>,
+        element_id: &ElementId,
+        size: &ThumbnailSize,
+    ) -> Result<Bytes> {
+        let url = Url::from_str(&format!(
+            "{}/thumbnails/d/{document_id}/{wvm}/e/{element_id}/s/{size}",
+            self.base_url,
+            wvm = wvm.into(),
+        ))?;
+        // Replaces the JSON accept header set by `request`
+        let headers =
+            HeaderMap::from_iter([(header::ACCEPT, HeaderValue::from_static("image/png"))]);
+        let res = self
+            .send(self.request(Method::GET, url).headers(headers))
+            .await?
+            .error_for_status()?;
+        ensure_png(res.bytes().await?, || {
+            format!("the thumbnail of {element_id}")
+        })
+    }
+
+    /// Renders a part from an isometric view, as a PNG of the given size. The thumbnails
+    /// API only renders whole tabs, so parts are rendered by the shaded views endpoint.
+    pub async fn get_part_shaded_view(
+        &self,
+        document_id: &DocumentId,
+        wvm: impl Into<Wvm>,
+        element_id: &ElementId,
+        part_id: &PartId,
+        size: &ThumbnailSize,
+    ) -> Result<Bytes> {
+        let mut url = Url::from_str(&format!(
+            "{}/parts/d/{document_id}/{wvm}/e/{element_id}/partid/{part_id}/shadedviews?",
+            self.base_url,
+            wvm = wvm.into(),
+        ))?;
+        {
+            let mut query = url.query_pairs_mut();
+            query.append_pair("outputWidth", &size.width.to_string());
+            query.append_pair("outputHeight", &size.height.to_string());
+            query.append_pair("pixelSize", "0");
+            query.append_pair("viewMatrix", "isometric");
+            query.append_pair("configuration", "");
+        }
+
+        let views: ShadedViews = self
+            .send(self.request(Method::GET, url))
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let image = views
+            .images
+            .first()
+            .ok_or_else(|| anyhow!("OnShape returned no image of part {part_id}"))?;
+        ensure_png(
+            base64::engine::general_purpose::STANDARD
+                .decode(image)?
+                .into(),
+            || format!("the image of part {part_id}"),
+        )
     }
 
-    pub fn get_part_parasolid(
+    pub async fn get_part_parasolid(
         &self,
-        document_id: &String,
-        microversion_id: &String,
-        element_id: &String,
-        part_id: &String,
-        configuration: &String,
+        document_id: &DocumentId,
+        microversion_id: &str,
+        element_id: &ElementId,
+        part_id: &PartId,
+        configuration: &str,
     ) -> Result<String> {
         let mut url = Url::from_str(&format!(
             "{}/parts/d/{document_id}/m/{microversion_id}/e/{element_id}/partid/{part_id}/parasolid?",
-            BASE_URL,
+            self.base_url,
             document_id = document_id,
             microversion_id = microversion_id,
             element_id = element_id,
@@ -177,7 +451,7 @@ impl OnShapeClient {
             query.append_pair("configuration", configuration);
         }
 
-        let res = self.request(Method::GET, url).send()?;
+        let res = self.send(self.request(Method::GET, url)).await?;
         assert!(res.status().is_redirection(), "Redirect expected");
 
         let redirect_url = res
@@ -186,7 +460,11 @@ impl OnShapeClient {
             .expect("Missing location header")
             .to_str()?;
 
-        let para_text = self.request(Method::GET, redirect_url).send()?.text()?;
+        let para_text = self
+            .send(self.request(Method::GET, redirect_url))
+            .await?
+            .text()
+            .await?;
 
         lazy_static! {
             // DATE=2023-06-22T10:00:01 (UTC);
@@ -196,33 +474,34 @@ impl OnShapeClient {
         Ok(HEADER_DATE_PATTERN.replace(&para_text, "").into())
     }
 
-    pub fn begin_translation(
+    pub async fn begin_translation(
         &self,
         format: &ExportFileFormat,
-        document_id: &String,
-        workspace_id: &String,
-        element_id: &String,
-        part_id: &String,
-        basename: &String,
+        document_id: &DocumentId,
+        wvm: impl Into<Wvm>,
+        element_id: &ElementId,
+        part_id: &PartId,
+        basename: &str,
     ) -> Result<TranslationJobWithOutput> {
         let output_filename =
             format!("{basename}.{extension}", extension = format.extension());
 
         let url = Url::from_str(&format!(
-            "{}/partstudios/d/{document_id}/w/{workspace_id}/e/{element_id}/translations",
-            BASE_URL,
+            "{}/partstudios/d/{document_id}/{wvm}/e/{element_id}/translations",
+            self.base_url,
+            wvm = wvm.into(),
         ))?;
         let req = self.request(Method::POST, url);
         let payload = TranslationRequest {
-            part_ids: part_id.into(),
+            part_ids: part_id.clone(),
             destination_name: output_filename.clone(),
-            format: format.clone(),
+            format: *format,
             configuration: "".into(),
             store_in_document: false,
             resolution: TranslationResolution::Fine,
 
             distance_tolerance: 0.00006,
-            angular_tolerance: 0.04363323129985824,
+            angular_tolerance: 0.043_633_23,
             maximum_chord_length: 10.,
             specify_units: true,
             units: TranslationUnit::Millimeters,
@@ -231,28 +510,32 @@ impl OnShapeClient {
             image_height: 96,
         };
 
-        let res = req.json(&payload).send()?;
-        let job: TranslationJob = res.json()?;
+        let res = self.send(req.json(&payload)).await?;
+        let job: TranslationJob = res.json().await?;
         Ok(TranslationJobWithOutput {
             job,
             output_filename: Utf8PathBuf::from_str(&output_filename.clone()).unwrap(),
-            format: format.clone(),
+            format: *format,
         })
     }
 
-    pub fn check_translation(
+    pub async fn check_translation(
         &self,
         job: &TranslationJobWithOutput,
     ) -> Result<TranslationJobWithOutput> {
-        let j: TranslationJob = self.request(Method::GET, job.url.clone()).send()?.json()?;
+        let j: TranslationJob = self
+            .send(self.request(Method::GET, job.url.clone()))
+            .await?
+            .json()
+            .await?;
         Ok(TranslationJobWithOutput {
             job: j,
             output_filename: job.output_filename.clone(),
-            format: job.format.clone(),
+            format: job.format,
         })
     }
 
-    pub fn download_translated_file(
+    pub async fn download_translated_file(
         &self,
         job: &TranslationJobWithOutput,
         strip_indeterminism: bool,
@@ -260,7 +543,7 @@ impl OnShapeClient {
         let url = match (job.request_state, job.result_external_data_ids.as_deref()) {
             (TranslationState::Done, Some([external_id, ..])) => Url::from_str(&format!(
                 "{}/documents/d/{document_id}/externaldata/{external_id}",
-                BASE_URL,
+                self.base_url,
                 document_id = job.document_id,
             ))?,
             _ => {
@@ -271,40 +554,97 @@ impl OnShapeClient {
             }
         };
 
-        eprintln!("Downloading file, {}", job.output_filename);
-        let res = self.request(Method::GET, url).send()?;
+        let res = self.send(self.request(Method::GET, url)).await?;
         if job.format == ExportFileFormat::Step && strip_indeterminism {
-            println!("Stripping indeterminism from .step file");
             lazy_static! {
                 // DATE=2023-06-22T10:00:01 (UTC);
                 static ref INDETERMINISTIC_FIELDS_PATTERN: Regex =
                     Regex::new(r"/\* name \*/.*,\s+/\* time_stamp \*/.*,\s+").unwrap();
             }
 
-            let file_text = res.text()?;
+            let file_text = res.text().await?;
             let corrected_text = INDETERMINISTIC_FIELDS_PATTERN.replace(&file_text, "");
             Ok(Bytes::copy_from_slice(corrected_text.as_bytes()))
         } else {
-            Ok(res.bytes()?)
+            Ok(res.bytes().await?)
         }
     }
 
-    pub fn request<U: IntoUrl>(&self, method: Method, url: U) -> RequestBuilder {
-        // TODO(shyndman): This works for now, but ideally should be refactored to:
-        //     1. perform rate limiting when the request is SENT
-        //     2. handle retries when the server rate limits a request
+    /// Sends a request built by [`OnShapeClient::request`], recording or replaying it if
+    /// the client was configured with a cassette.
+    pub async fn send(&self, req: RequestBuilder) -> Result<Response> {
+        let req = req.build()?;
+        match self.cassette {
+            Some(ref cassette) if cassette.is_replaying() => {
+                log::debug!("{} {} (replayed)", req.method(), req.url().path());
+                cassette.execute(req, |_| async { unreachable!() }).await
+            }
+            Some(ref cassette) => cassette.execute(req, |req| self.send_now(req)).await,
+            None => self.send_now(req).await,
+        }
+    }
+
+    async fn send_now(&self, req: Request) -> Result<Response> {
+        let mut retries = 0;
         loop {
-            match self.rate_limiter.check() {
-                Ok(_) => break,
-                Err(negative) => {
-                    let wait_duration = negative.wait_time_from(QuantaClock::default().now());
-                    // eprintln!("Rate limiting for {}ms", wait_duration.as_millis());
-                    std::thread::sleep(wait_duration);
-                }
+            let mut attempt = req
+                .try_clone()
+                .ok_or_else(|| anyhow!("Streaming request bodies are not supported"))?;
+
+            let rate_limit_start = Instant::now();
+            self.rate_limiter.until_ready().await;
+            let rate_limit_wait = rate_limit_start.elapsed();
+
+            // Requests are signed as late as possible, because OnShape rejects stale dates and
+            // reused nonces
+            self.sign(&mut attempt);
+            log::trace!(
+                "{} {} {:?}",
+                attempt.method(),
+                attempt.url(),
+                redacted_headers(attempt.headers())
+            );
+
+            let (method, path) = (attempt.method().clone(), attempt.url().path().to_owned());
+            let start = Instant::now();
+            let res = self.http_client.execute(attempt).await?;
+            log::debug!(
+                "{method} {path} {status} in {latency}ms (retries: {retries}, rate limit \
+                 wait: {wait}ms)",
+                status = res.status(),
+                latency = start.elapsed().as_millis(),
+                wait = rate_limit_wait.as_millis(),
+            );
+
+            if res.status() == StatusCode::TOO_MANY_REQUESTS && retries < MAX_RETRIES {
+                let delay = retry_after(&res).unwrap_or(RETRY_BACKOFF * 2u32.pow(retries));
+                log::debug!(
+                    "Rate limited by OnShape, retrying in {}ms",
+                    delay.as_millis()
+                );
+                sleep(delay).await;
+                retries += 1;
+                continue;
             }
+            return Ok(res);
         }
+    }
+
+    /// Creates a request to the OnShape API. The request is signed when it is passed to
+    /// [`OnShapeClient::send`].
+    pub fn request<U: IntoUrl>(&self, method: Method, url: U) -> RequestBuilder {
+        self.http_client
+            .request(method, url)
+            .header(
+                header::ACCEPT,
+                "application/vnd.onshape.v2+json;charset=UTF-8;qs=0.2",
+            )
+            .header(header::CONTENT_TYPE, mime::APPLICATION_JSON.to_string())
+    }
 
-        let url = url.into_url().expect("Could not convert to URL");
+    fn sign(&self, req: &mut Request) {
+        let method = req.method();
+        let url = req.url();
         let content_type = mime::APPLICATION_JSON;
 
         // Prepare the signature
@@ -340,19 +680,65 @@ impl OnShapeClient {
                 base64::engine::general_purpose::STANDARD.encode(mac.finalize().into_bytes())
         );
 
-        self.http_client
-            .request(method, url)
-            .header(header::AUTHORIZATION, authorization_val)
-            .header(
-                header::ACCEPT,
-                "application/vnd.onshape.v2+json;charset=UTF-8;qs=0.2",
-            )
-            .header(header::CONTENT_TYPE, content_type.to_string())
-            .header(header::DATE, date)
-            .header("On-Nonce", nonce)
+        let headers = req.headers_mut();
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_str(&authorization_val).expect("Signature is valid ASCII"),
+        );
+        headers.insert(
+            header::DATE,
+            HeaderValue::from_str(&date).expect("Date is valid ASCII"),
+        );
+        headers.insert(
+            "On-Nonce",
+            HeaderValue::from_str(&nonce).expect("Nonce is alphanumeric"),
+        );
+    }
+}
+
+/// Formats `headers` for logging or storage, hiding the request signature
+pub(crate) fn redacted_headers(headers: &HeaderMap) -> BTreeMap<String, String> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = String::from_utf8_lossy(value.as_bytes());
+            // The whole header is hidden, as it contains the access key, and cassettes
+            // are meant to be shared
+            let value = if name == header::AUTHORIZATION {
+                REDACTED_VALUE.into()
+            } else {
+                value.into_owned()
+            };
+            (name.to_string(), value)
+        })
+        .collect()
+}
+
+/// Fails unless `bytes` is a PNG, so that an unexpected response is never written to disk as
+/// an image. `describe` names the image in the error.
+fn ensure_png(bytes: Bytes, describe: impl FnOnce() -> String) -> Result<Bytes> {
+    const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+    if bytes.starts_with(PNG_SIGNATURE) {
+        Ok(bytes)
+    } else {
+        Err(anyhow!(
+            "OnShape returned something other than a PNG for {}",
+            describe()
+        ))
     }
 }
 
+/// Reads the delay requested by a `Retry-After` header, if it is expressed in seconds
+fn retry_after(res: &Response) -> Option<Duration> {
+    res.headers()
+        .get(header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()
+        .map(Duration::from_secs)
+}
+
 fn create_nonce() -> String {
     thread_rng()
         .sample_iter(&Alphanumeric)
@@ -363,9 +749,60 @@ fn create_nonce() -> String {
 
 #[cfg(test)]
 mod test {
+    use std::sync::Mutex;
+
     use indoc::indoc;
     use regex::Regex;
 
+    use super::*;
+    use crate::onshape::cassette::{Interaction, RecordedRequest, RecordedResponse};
+
+    #[tokio::test]
+    async fn test_thumbnails_must_be_pngs() {
+        let url = "https://cad.onshape.com/api/thumbnails/d/6a1de9567f16cd7d1f564dbb/w/\
+                   da2053ce82593768f41daab4/e/59628be740b2cf41a42e4c8a/s/300x300";
+        let interaction = |status: u16, body: &[u8]| {
+            Some(Interaction {
+                request: RecordedRequest {
+                    method: "GET".into(),
+                    url: url.into(),
+                    headers: BTreeMap::new(),
+                    body: None,
+                },
+                response: RecordedResponse {
+                    status,
+                    headers: BTreeMap::new(),
+                    body_base64: base64::engine::general_purpose::STANDARD.encode(body),
+                },
+            })
+        };
+        let png = b"\x89PNG\r\n\x1a\nIHDR";
+        let client = OnShapeClient::builder()
+            .cassette(Some(Cassette::Replay {
+                dir: "cassette".into(),
+                interactions: Mutex::new(vec![
+                    interaction(404, br#"{"message":"Not found"}"#),
+                    interaction(200, br#"{"message":"Not an image"}"#),
+                    interaction(200, png),
+                ]),
+            }))
+            .build()
+            .unwrap();
+
+        let document_id = DocumentId::from("6a1de9567f16cd7d1f564dbb");
+        let workspace_id = WorkspaceId::from("da2053ce82593768f41daab4");
+        let element_id = ElementId::from("59628be740b2cf41a42e4c8a");
+        let size = ThumbnailSize {
+            width: 300,
+            height: 300,
+        };
+        let thumbnail =
+            || client.get_element_thumbnail(&document_id, &workspace_id, &element_id, &size);
+        assert!(thumbnail().await.is_err());
+        assert!(thumbnail().await.is_err());
+        assert_eq!(&thumbnail().await.unwrap()[..], png);
+    }
+
     #[test]
     fn test_strip_step_determinism() {
         let step = indoc! {r"
@@ -0,0 +1,126 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, Error, Result};
+use url::Url;
+
+use super::{DocumentId, ElementId, VersionId, WorkspaceId};
+
+/// The IDs found in the URL of an OnShape document, as copied from the browser's address
+/// bar. For example,
+/// `https://cad.onshape.com/documents/<document>/w/<workspace>/e/<element>`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DocumentUrl {
+    pub document_id: DocumentId,
+    pub workspace_id: Option<WorkspaceId>,
+    pub version_id: Option<VersionId>,
+    /// Found in some shared links, which refer to the document as it was at a microversion
+    pub microversion_id: Option<String>,
+    pub element_id: Option<ElementId>,
+}
+
+impl DocumentUrl {
+    /// Returns `true` if `s` looks like a URL rather than a bare ID
+    pub fn is_url(s: &str) -> bool {
+        s.starts_with("https://") || s.starts_with("http://")
+    }
+
+    /// Parses a document ID, or the URL of a document. Suitable for use as a clap
+    /// `value_parser`.
+    pub fn parse_document_id(s: &str) -> Result<DocumentId> {
+        if DocumentUrl::is_url(s) {
+            Ok(s.parse::<DocumentUrl>()?.document_id)
+        } else {
+            Ok(s.into())
+        }
+    }
+}
+
+impl FromStr for DocumentUrl {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let url = Url::parse(s.trim()).map_err(|e| anyhow!("Invalid URL \"{s}\": {e}"))?;
+        let malformed = || {
+            anyhow!(
+                "\"{s}\" is not an OnShape document URL. Expected something like \
+                 https://cad.onshape.com/documents/<document>/w/<workspace>/e/<element>"
+            )
+        };
+
+        let mut segments = url
+            .path_segments()
+            .ok_or_else(malformed)?
+            .filter(|s| !s.is_empty());
+        if segments.next() != Some("documents") {
+            return Err(malformed());
+        }
+
+        let mut parsed = DocumentUrl {
+            document_id: segments.next().ok_or_else(malformed)?.into(),
+            ..Default::default()
+        };
+        while let Some(kind) = segments.next() {
+            let id = segments.next().ok_or_else(malformed)?;
+            match kind {
+                "w" => parsed.workspace_id = Some(id.into()),
+                "v" => parsed.version_id = Some(id.into()),
+                "m" => parsed.microversion_id = Some(id.into()),
+                "e" => parsed.element_id = Some(id.into()),
+                _ => return Err(malformed()),
+            }
+        }
+
+        Ok(parsed)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_document_url() {
+        let url: DocumentUrl = concat!(
+            "https://cad.onshape.com/documents/6a1de9567f16cd7d1f564dbb",
+            "/w/da2053ce82593768f41daab4/e/59628be740b2cf41a42e4c8a"
+        )
+        .parse()
+        .unwrap();
+        assert_eq!(
+            url,
+            DocumentUrl {
+                document_id: "6a1de9567f16cd7d1f564dbb".into(),
+                workspace_id: Some("da2053ce82593768f41daab4".into()),
+                version_id: None,
+                microversion_id: None,
+                element_id: Some("59628be740b2cf41a42e4c8a".into()),
+            }
+        );
+
+        let url: DocumentUrl = concat!(
+            "https://cad.onshape.com/documents/6a1de9567f16cd7d1f564dbb",
+            "/w/da2053ce82593768f41daab4/m/0d1e2f3a4b5c6d7e8f9a0b1c/e/59628be740b2cf41a42e4c8a"
+        )
+        .parse()
+        .unwrap();
+        assert_eq!(
+            url.microversion_id.as_deref(),
+            Some("0d1e2f3a4b5c6d7e8f9a0b1c")
+        );
+
+        let url: DocumentUrl = "https://cad.onshape.com/documents/6a1de9567f16cd7d1f564dbb"
+            .parse()
+            .unwrap();
+        assert_eq!(url.document_id.as_str(), "6a1de9567f16cd7d1f564dbb");
+        assert_eq!(url.workspace_id, None);
+
+        assert!("https://cad.onshape.com/parts/6a1de9567f16cd7d1f564dbb"
+            .parse::<DocumentUrl>()
+            .is_err());
+        assert!(
+            "https://cad.onshape.com/documents/6a1de9567f16cd7d1f564dbb/w"
+                .parse::<DocumentUrl>()
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,126 @@
+use std::{
+    borrow::Borrow,
+    fmt::{self, Display, Formatter},
+    ops::Deref,
+};
+
+use serde::{Deserialize, Serialize};
+
+macro_rules! define_id {
+    ($(#[$meta:meta])* $name:ident) => {
+        $(#[$meta])*
+        #[derive(
+            Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize, Serialize,
+        )]
+        #[serde(transparent)]
+        pub struct $name(String);
+
+        impl $name {
+            pub fn new(id: impl Into<String>) -> Self {
+                Self(id.into())
+            }
+
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+
+            pub fn into_string(self) -> String {
+                self.0
+            }
+        }
+
+        impl Display for $name {
+            fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+
+        impl Deref for $name {
+            type Target = str;
+
+            fn deref(&self) -> &Self::Target {
+                &self.0
+            }
+        }
+
+        impl AsRef<str> for $name {
+            fn as_ref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl Borrow<str> for $name {
+            fn borrow(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl From<String> for $name {
+            fn from(id: String) -> Self {
+                Self(id)
+            }
+        }
+
+        impl From<&str> for $name {
+            fn from(id: &str) -> Self {
+                Self(id.into())
+            }
+        }
+    };
+}
+
+define_id!(
+    /// Identifies an OnShape document
+    DocumentId
+);
+define_id!(
+    /// Identifies a workspace (a mutable branch) of an OnShape document
+    WorkspaceId
+);
+define_id!(
+    /// Identifies an immutable, named version of an OnShape document
+    VersionId
+);
+define_id!(
+    /// Identifies a tab (part studio, assembly, drawing, etc.) within an OnShape document
+    ElementId
+);
+define_id!(
+    /// Identifies a part within a part studio
+    PartId
+);
+
+/// The state of a document that a request reads from: the current state of a workspace, or
+/// an immutable version. Written in URLs as OnShape's `{wvm}/{wvmid}` path segments.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Wvm {
+    Workspace(WorkspaceId),
+    Version(VersionId),
+}
+
+impl Display for Wvm {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Wvm::Workspace(id) => write!(f, "w/{id}"),
+            Wvm::Version(id) => write!(f, "v/{id}"),
+        }
+    }
+}
+
+impl From<&WorkspaceId> for Wvm {
+    fn from(id: &WorkspaceId) -> Self {
+        Wvm::Workspace(id.clone())
+    }
+}
+
+impl From<&VersionId> for Wvm {
+    fn from(id: &VersionId) -> Self {
+        Wvm::Version(id.clone())
+    }
+}
+
+impl From<&Wvm> for Wvm {
+    fn from(wvm: &Wvm) -> Self {
+        wvm.clone()
+    }
+}
@@ -1,18 +1,43 @@
+//! A client for the subset of the OnShape REST API used by offshape, along with typed
+//! models of the documents, elements, parts and versions it returns.
+
+pub mod blocking;
+pub mod cassette;
 pub mod client;
+pub mod document_url;
+pub mod ids;
 pub mod models;
 
 use anyhow::Result;
-use dotenv::dotenv;
-use url::Url;
 
-use self::client::OnShapeClient;
+use self::cassette::Cassette;
+pub use self::{
+    client::{OnShapeClient, OnShapeClientBuilder},
+    document_url::DocumentUrl,
+    ids::{DocumentId, ElementId, PartId, VersionId, WorkspaceId, Wvm},
+};
+use crate::GlobalOptions;
 
-pub(crate) fn environment_client(proxy_url: Option<Url>) -> Result<OnShapeClient> {
-    dotenv().ok();
+/// Builds a client from the environment's credentials and the CLI's global options, talking
+/// to the API at `base_url` if one is configured
+pub(crate) fn environment_client(
+    global_options: &GlobalOptions,
+    base_url: Option<&str>,
+) -> Result<OnShapeClient> {
+    let cassette = match (&global_options.record_dir, &global_options.replay_dir) {
+        (Some(dir), _) => Some(Cassette::record(dir)?),
+        (_, Some(dir)) => Some(Cassette::replay(dir)?),
+        _ => None,
+    };
 
-    OnShapeClient::new(
-        std::env::var("ONSHAPE_ACCESS_KEY")?,
-        std::env::var("ONSHAPE_SECRET_KEY")?,
-        proxy_url,
-    )
+    let mut builder = OnShapeClient::builder()
+        .proxy_url(global_options.proxy_url.clone())
+        .cassette(cassette);
+    if let Some(base_url) = base_url {
+        builder = builder.base_url(base_url);
+    }
+    if global_options.replay_dir.is_none() {
+        builder = builder.credentials_from_env()?;
+    }
+    builder.build()
 }
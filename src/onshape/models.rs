@@ -1,26 +1,72 @@
-use std::{ops::Deref, slice::Iter};
+use std::{
+    fmt::{self, Display, Formatter},
+    ops::Deref,
+    slice::Iter,
+    str::FromStr,
+};
 
+use anyhow::anyhow;
 use camino::Utf8PathBuf;
 use reqwest::Url;
-use serde::{Deserialize, Serialize};
-#[derive(Debug, Deserialize)]
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+
+pub use super::ids::{DocumentId, ElementId, PartId, VersionId, WorkspaceId, Wvm};
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Document {
-    pub id: String,
+    pub id: DocumentId,
     pub name: String,
     #[serde(rename = "defaultWorkspace")]
     pub default_workspace: Workspace,
 }
 
-#[derive(Debug, Deserialize)]
+/// One page of the results of a document search
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct DocumentPage {
+    pub items: Vec<Document>,
+    /// The URL of the next page of results, if there is one
+    pub next: Option<Url>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Workspace {
-    pub id: String,
+    pub id: WorkspaceId,
     pub name: String,
     pub href: Url,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Version {
+    pub id: VersionId,
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(rename = "documentId")]
+    pub document_id: DocumentId,
+    #[serde(rename = "microversion")]
+    pub microversion_id: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct VersionRequest {
+    #[serde(rename = "documentId")]
+    pub document_id: DocumentId,
+    #[serde(rename = "workspaceId")]
+    pub workspace_id: WorkspaceId,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+/// Identifies a workspace's state at a point in time. A new microversion is created by
+/// every change made to the workspace.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Microversion {
+    pub microversion: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct DocumentElement {
-    pub id: String,
+    pub id: ElementId,
     pub name: String,
     #[serde(rename = "filename")]
     pub file_name: Option<String>,
@@ -28,15 +74,62 @@ pub struct DocumentElement {
     pub element_type: TabElementType,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Part {
     pub name: String,
     #[serde(rename = "partId")]
-    pub part_id: String,
+    pub part_id: PartId,
     #[serde(rename = "elementId")]
-    pub element_id: String,
+    pub element_id: ElementId,
     #[serde(rename = "microversionId")]
     pub microversion_id: String,
+    /// The kind of body, like "solid", "sheet" or "wire"
+    #[serde(rename = "bodyType", default)]
+    pub body_type: String,
+}
+
+/// The dimensions of a rendered image, in pixels, written as `WIDTHxHEIGHT`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ThumbnailSize {
+    pub width: u32,
+    pub height: u32,
+}
+impl FromStr for ThumbnailSize {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let size = s
+            .split_once('x')
+            .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)));
+        match size {
+            Some((width, height)) if width > 0 && height > 0 => Ok(Self { width, height }),
+            _ => Err(anyhow!("{s:?} isn't an image size, like \"300x300\"")),
+        }
+    }
+}
+impl Display for ThumbnailSize {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+impl Serialize for ThumbnailSize {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+impl<'de> Deserialize<'de> for ThumbnailSize {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(D::Error::custom)
+    }
+}
+
+/// Renderings of a part, as returned by the shaded views endpoint
+#[derive(Clone, Debug, Deserialize)]
+pub struct ShadedViews {
+    /// Base64 encoded PNGs, one per requested view
+    pub images: Vec<String>,
 }
 
 #[derive(Clone, Debug, Serialize)]
@@ -44,7 +137,7 @@ pub struct TranslationRequest {
     #[serde(rename = "formatName")]
     pub format: ExportFileFormat,
     #[serde(rename = "partIds")]
-    pub part_ids: String,
+    pub part_ids: PartId,
     #[serde(rename = "destinationName")]
     pub destination_name: String,
     #[serde(rename = "storeInDocument")]
@@ -110,6 +203,15 @@ impl ExportFileFormat {
         .into()
     }
 
+    /// Serializes the format as its extension, as it's spelled in offshape.toml. For use in
+    /// offshape's own output, with `#[serde(serialize_with)]`.
+    pub fn serialize_extension<S: Serializer>(
+        format: &ExportFileFormat,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format.extension())
+    }
+
     pub fn export_action(&self) -> ExportAction {
         match self {
             ExportFileFormat::ThreeMF => ExportAction::Translate,
@@ -157,8 +259,9 @@ impl Deref for TranslationJobWithOutput {
     }
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct TranslationJob {
+    pub id: String,
     pub name: String,
     #[serde(rename = "href")]
     pub url: Url,
@@ -167,12 +270,12 @@ pub struct TranslationJob {
     #[serde(rename = "failureReason")]
     pub failure_reason: Option<String>,
     #[serde(rename = "documentId")]
-    pub document_id: String,
+    pub document_id: DocumentId,
     #[serde(rename = "resultExternalDataIds")]
     pub result_external_data_ids: Option<Vec<String>>,
 }
 
-#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
 pub enum TranslationState {
     #[serde(rename = "ACTIVE")]
     Active,
@@ -182,7 +285,9 @@ pub enum TranslationState {
     Failed,
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(
+    Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq, Hash, PartialOrd, Ord,
+)]
 pub enum TabElementType {
     #[serde(rename = "APPLICATION")]
     Application,
@@ -208,10 +313,32 @@ pub enum TabElementType {
     Unknown,
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
 pub enum InstanceType {
     Assembly,
     Feature,
     Part,
     Unknown,
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_thumbnail_size() {
+        let size: ThumbnailSize = "1200x900".parse().unwrap();
+        assert_eq!(
+            size,
+            ThumbnailSize {
+                width: 1200,
+                height: 900
+            }
+        );
+        assert_eq!(size.to_string(), "1200x900");
+
+        for invalid in ["1200", "1200x", "x900", "0x900", "1200 x 900", "wide"] {
+            assert!(invalid.parse::<ThumbnailSize>().is_err(), "{invalid}");
+        }
+    }
+}
@@ -1,35 +1,73 @@
 use std::{
-    collections::HashMap,
-    fs::{create_dir_all, File},
-    io::Write,
-    time::SystemTime,
+    collections::HashSet,
+    fmt::{self, Display, Formatter},
+    fs::{create_dir_all, remove_dir_all, remove_file, rename, File},
+    io::{self, Write},
+    time::{Duration, SystemTime},
 };
 
 use anyhow::{anyhow, Result};
-use camino::Utf8PathBuf;
+use bytes::Bytes;
+use camino::{Utf8Path, Utf8PathBuf};
 use clap::Args;
 use convert_case::{Case, Casing};
+use futures::future::try_join_all;
 use itertools::Itertools;
+use serde::Serialize;
+use tokio::time::sleep;
 
 use crate::{
-    config::{SyncConfig, SyncedDocument},
+    config::{Hooks, SyncConfig, SyncedDocument},
+    git,
+    hooks::run_hook,
     onshape::{
         environment_client,
         models::{
-            ExportAction, ExportFileFormat, TranslationJobWithOutput, TranslationState,
+            ExportAction, ExportFileFormat, Part, ThumbnailSize, TranslationJobWithOutput,
+            TranslationState,
         },
+        ElementId, OnShapeClient, PartId,
     },
+    report::{PullEvent, PullReporter, ReporterKind},
+    state::{relative_path, FileRecord, PullState},
     GlobalOptions,
 };
 
+/// The outcome of exporting a single file: where it was written and the record of it, or
+/// where it would have been written and OnShape's explanation of why it wasn't
+type ExportResult = std::result::Result<(Utf8PathBuf, FileRecord), (Utf8PathBuf, String)>;
+
+/// How long to wait between checks on an active translation job
+const TRANSLATION_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
 #[derive(Args, Debug)]
 pub struct PullOptions {
+    /// Keeps files from earlier pulls whose parts no longer exist. Files that offshape
+    /// didn't write are never removed.
     #[arg(long)]
     pub no_clean_paths: bool,
     /// If `true` (the default), files will be stripped of timestamps relating to their
     /// export time, or the time they were written to disk.
     #[arg(long, default_value_t = true)]
     pub strip_indeterminism: bool,
+    /// How progress is reported while pulling
+    #[arg(long, default_value_t = ReporterKind::Auto)]
+    pub reporter: ReporterKind,
+    /// Only exports parts that have changed since they were last pulled
+    #[arg(long)]
+    pub incremental: bool,
+    /// Moves the files that exported successfully into place, even if others failed. By
+    /// default, a failed export leaves every file unchanged.
+    #[arg(long)]
+    pub allow_partial: bool,
+    /// Commits the files written and deleted by the pull to git, describing the parts that
+    /// changed and the document's microversion. The git index must have nothing staged.
+    #[arg(long, conflicts_with = "dry_run")]
+    pub commit: bool,
+    /// Prints the files that would be exported and deleted, without exporting or deleting
+    /// anything
+    #[arg(long)]
+    pub dry_run: bool,
 }
 impl PullOptions {
     fn should_clean_paths(&self) -> bool {
@@ -41,160 +79,775 @@ impl Default for PullOptions {
         Self {
             no_clean_paths: false,
             strip_indeterminism: true,
+            reporter: ReporterKind::Auto,
+            incremental: false,
+            allow_partial: false,
+            commit: false,
+            dry_run: false,
         }
     }
 }
 
-pub fn export(
+/// The outcome of a [`pull`]
+#[derive(Clone, Debug, Default)]
+pub struct PullSummary {
+    /// The files that were written to disk
+    pub written: Vec<Utf8PathBuf>,
+    /// The thumbnails that were written to disk
+    pub images: Vec<Utf8PathBuf>,
+    /// The files from earlier pulls that were deleted
+    pub deleted: Vec<Utf8PathBuf>,
+    /// The files that could not be exported, along with OnShape's explanation
+    pub failed: Vec<(Utf8PathBuf, String)>,
+}
+
+/// Pulls the parts described by `config`, reporting progress using the reporter selected in
+/// `options`. A dry run prints the pull's plan instead.
+pub async fn export(
     config: SyncConfig,
     global_options: GlobalOptions,
     options: PullOptions,
 ) -> Result<()> {
-    // Load the manifest describing what to pull
-    let SyncConfig {
-        document:
-            SyncedDocument {
-                id: ref document_id,
-                ref workspace_id,
-            },
-        ..
-    } = config;
-    let part_studios = config.part_studios.clone();
-    let formats = config.export_formats();
-
-    let client = environment_client(global_options.proxy_url)?;
-
-    // Validate that the part studios and parts exist
-    let element_map = client.get_document_elements(&document_id, &workspace_id)?;
-    let mut to_export_by_studio = HashMap::new();
-    for synced_studio in part_studios.iter() {
-        if !element_map.contains_key(&synced_studio.id) {
-            return Err(anyhow!(
-                "Could not find a part studio ({})",
-                synced_studio.id
-            ));
-        }
-
-        let studio_parts: Vec<(String, String)> = client
-            .get_studio_parts(&document_id, &workspace_id, &synced_studio.id)?
-            .iter()
-            .map(|p| {
-                let basename = p.name.to_case(Case::Snake);
-                (p.part_id.clone(), basename)
-            })
-            .collect();
-        to_export_by_studio.insert(&synced_studio.id, studio_parts);
+    let client = environment_client(&global_options, config.base_url.as_deref())?;
+    if options.dry_run {
+        let reporter = options.reporter.reporter();
+        let res = plan_pull(&client, &config, &options, reporter.as_ref()).await;
+        reporter.finish();
+        return print_plan(&res?, options.reporter);
     }
 
-    // Create/clean output directories
-    for f in formats.iter() {
-        let path = config.format_path(f);
-        if let Some(path) = path {
-            create_dir_all(&(*path.clone()))?;
-            if options.should_clean_paths() {
-                clean_path(&path, &f.extension());
-            }
+    // The workspace's state is captured before pulling, so the commit never claims to
+    // include changes made during the pull
+    let commit_source = if options.commit {
+        git::ensure_nothing_staged(&config.root).await?;
+        let SyncedDocument {
+            ref id,
+            ref workspace_id,
+            ..
+        } = config.document;
+        Some((
+            client.get_document(id).await?,
+            client.get_current_microversion(id, workspace_id).await?,
+        ))
+    } else {
+        None
+    };
+
+    let reporter = options.reporter.reporter();
+    let res = pull(&client, &config, &options, reporter.as_ref()).await;
+    reporter.finish();
+    let summary = res?;
+
+    if let Some((document, microversion)) = commit_source {
+        git::commit_pull(&config, &summary, &document, &microversion).await?;
+    }
+    Ok(())
+}
+
+/// What a pull will do to a single file
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum PlannedAction {
+    /// The part will be exported to `output`, replacing any file already there
+    Export {
+        output: Utf8PathBuf,
+        #[serde(serialize_with = "ExportFileFormat::serialize_extension")]
+        format: ExportFileFormat,
+        studio_id: ElementId,
+        part_id: PartId,
+        part_name: String,
+        microversion_id: String,
+    },
+    /// An image of the part, or of the whole studio if `part_id` is `None`, will be rendered
+    /// to `output`
+    Thumbnail {
+        output: Utf8PathBuf,
+        studio_id: ElementId,
+        part_id: Option<PartId>,
+        size: ThumbnailSize,
+    },
+    /// The file was written by an earlier pull, but no longer belongs to any part, and will
+    /// be deleted
+    Delete { path: Utf8PathBuf },
+    /// The file will be left alone, because its part is unchanged, or it doesn't belong to
+    /// any part
+    Skip { path: Utf8PathBuf, reason: String },
+}
+
+impl PlannedAction {
+    pub fn path(&self) -> &Utf8Path {
+        match self {
+            PlannedAction::Export { output, .. } |
+            PlannedAction::Thumbnail { output, .. } => output,
+            PlannedAction::Delete { path } | PlannedAction::Skip { path, .. } => path,
         }
     }
+}
 
-    // Begin translating the parts
-    let mut active_jobs = vec![];
-    for part_studio in part_studios.iter() {
-        let to_sync = to_export_by_studio.get(&part_studio.id).unwrap();
-        for (part_id, basename) in to_sync {
-            // Begin translations for the formats that require them
-            for f in formats
-                .iter()
-                .filter(|f| f.export_action() == ExportAction::Translate)
-            {
-                eprintln!("Exporting {}.{}", basename, f.extension());
-                active_jobs.push(client.begin_translation(
-                    &f,
-                    &document_id,
-                    &workspace_id,
-                    &part_studio.id,
-                    &part_id,
-                    &basename,
-                )?);
+impl Display for PlannedAction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            PlannedAction::Export {
+                output, part_name, ..
+            } => write!(f, "export  {output} ({part_name})"),
+            PlannedAction::Thumbnail { output, .. } => write!(f, "image   {output}"),
+            PlannedAction::Delete { path } => write!(f, "delete  {path}"),
+            PlannedAction::Skip { path, reason } => write!(f, "skip    {path} ({reason})"),
+        }
+    }
+}
+
+/// Everything a pull will do, worked out before anything is exported or removed
+#[derive(Clone, Debug)]
+pub struct PullPlan {
+    /// The config the plan was made from, with its part studios resolved
+    pub config: SyncConfig,
+    pub actions: Vec<PlannedAction>,
+}
+
+/// Exports every part in the configured part studios to each of the configured formats'
+/// paths. Progress is described to `reporter` as the pull proceeds.
+pub async fn pull(
+    client: &OnShapeClient,
+    config: &SyncConfig,
+    options: &PullOptions,
+    reporter: &dyn PullReporter,
+) -> Result<PullSummary> {
+    let plan = plan_pull(client, config, options, reporter).await?;
+    execute_plan(client, &plan, options, reporter).await
+}
+
+/// Resolves the configured part studios and lists their parts, to determine the file
+/// every part will be exported to, and which existing files will be removed. Nothing is
+/// written to disk, and no translations are started.
+pub async fn plan_pull(
+    client: &OnShapeClient,
+    config: &SyncConfig,
+    options: &PullOptions,
+    reporter: &dyn PullReporter,
+) -> Result<PullPlan> {
+    // Find the part studios, and validate that they exist
+    let config = config.resolved(client).await?;
+    let wvm = config.document.wvm();
+    let mut studio_parts = vec![];
+    for synced_studio in config.part_studios.iter() {
+        let parts = client
+            .get_studio_parts(&config.document.id, &wvm, &synced_studio.id)
+            .await?;
+        reporter.report(PullEvent::StudioResolved {
+            studio_id: synced_studio.id.clone(),
+            display_name: synced_studio.display_name.clone(),
+            part_count: parts.len(),
+        });
+        studio_parts.push(parts);
+    }
+
+    let state = PullState::load(&config.root)?;
+    let actions = plan_actions(&config, &state, &studio_parts, options, reporter);
+    Ok(PullPlan { config, actions })
+}
+
+/// Works out what a pull will do, given the parts in each of `config`'s part studios (in
+/// the order they're configured) and the record of the last pull
+fn plan_actions(
+    config: &SyncConfig,
+    state: &PullState,
+    studio_parts: &[Vec<Part>],
+    options: &PullOptions,
+    reporter: &dyn PullReporter,
+) -> Vec<PlannedAction> {
+    let SyncedDocument {
+        id: ref document_id,
+        ref workspace_id,
+        ..
+    } = config.document;
+    let incremental = options.incremental &&
+        state.document_id == *document_id &&
+        state.workspace_id == *workspace_id;
+
+    // Thumbnails are rendered again whenever their part or studio is exported, or if
+    // they're missing
+    let thumbnail_action = |output: Utf8PathBuf, size, changed: bool, studio_id, part_id| {
+        if incremental && !changed && output.exists() {
+            PlannedAction::Skip {
+                path: output,
+                reason: "unchanged".into(),
+            }
+        } else {
+            PlannedAction::Thumbnail {
+                output,
+                studio_id,
+                part_id,
+                size,
             }
+        }
+    };
 
-            for f in formats
-                .iter()
-                .copied()
-                .filter(|f| f.export_action() == ExportAction::Direct)
-            {
-                eprintln!("Exporting {}.{}", basename, f.extension());
-                if *f == ExportFileFormat::Stl {
-                    let stl_contents = client.get_part_stl(
-                        &document_id,
-                        &workspace_id,
-                        &part_studio.id,
-                        &part_id,
-                    )?;
-
-                    // TODO(shyndman): Figure out how to merge the STL file writes with
-                    // the 3mf and step files
-                    let mut output_path: Utf8PathBuf = config.format_path(f).unwrap().into();
-                    output_path.push(format!("{basename}.{ext}", ext = f.extension()));
-                    write_output_file(
-                        output_path,
-                        stl_contents.as_bytes(),
-                        options.strip_indeterminism,
-                    )?;
+    let mut actions = vec![];
+    for (synced_studio, parts) in config.part_studios.iter().zip(studio_parts) {
+        let studio_outputs = config.studio_outputs(synced_studio);
+        let mut studio_changed = false;
+        for part in parts {
+            let basename = part_basename(&part.name);
+            let mut outputs = vec![];
+            for (format, dir) in studio_outputs.iter() {
+                let output = output_path(dir, format, &basename);
+                let unchanged = incremental &&
+                    output.exists() &&
+                    state
+                        .file(&config.root, &output)
+                        .is_some_and(|r| r.microversion_id == part.microversion_id);
+                if unchanged {
+                    actions.push(PlannedAction::Skip {
+                        path: output,
+                        reason: "unchanged".into(),
+                    });
+                    continue;
+                }
+                outputs.push(output.clone());
+                actions.push(PlannedAction::Export {
+                    output,
+                    format: *format,
+                    studio_id: synced_studio.id.clone(),
+                    part_id: part.part_id.clone(),
+                    part_name: part.name.clone(),
+                    microversion_id: part.microversion_id.clone(),
+                });
+            }
+            let changed = !outputs.is_empty();
+            studio_changed |= changed;
+            if let Some(ref thumbnails) = config.thumbnails {
+                for size in thumbnails.sizes.iter() {
+                    actions.push(thumbnail_action(
+                        thumbnail_path(&thumbnails.path, &basename, size),
+                        *size,
+                        changed,
+                        synced_studio.id.clone(),
+                        Some(part.part_id.clone()),
+                    ));
                 }
             }
+            if changed {
+                reporter.report(PullEvent::PartDiscovered {
+                    studio_id: synced_studio.id.clone(),
+                    part_id: part.part_id.clone(),
+                    name: part.name.clone(),
+                    outputs,
+                });
+            }
+        }
+
+        if let Some(ref thumbnails) = config.thumbnails {
+            // Part basenames are snake case, so can't collide with a studio's
+            let basename = format!("studio-{}", part_basename(&synced_studio.display_name));
+            for size in thumbnails.sizes.iter() {
+                actions.push(thumbnail_action(
+                    thumbnail_path(&thumbnails.path, &basename, size),
+                    *size,
+                    studio_changed,
+                    synced_studio.id.clone(),
+                    None,
+                ));
+            }
         }
     }
 
-    // Check on the translation jobs repeatedly
-    while !active_jobs.is_empty() {
-        let mut next: Vec<TranslationJobWithOutput> = vec![];
-        for (group, jobs) in active_jobs
-            .iter()
-            .map(|j| client.check_translation(j).unwrap())
-            .group_by(|j| j.request_state)
-            .into_iter()
-        {
-            match group {
-                TranslationState::Active => {
-                    next = jobs.collect();
+    // Files in the output directories that no part will be exported to. Only files that
+    // an earlier pull wrote are removed.
+    let outputs: HashSet<Utf8PathBuf> =
+        actions.iter().map(|a| a.path().to_path_buf()).collect();
+    for (format, dir) in config.output_dirs() {
+        for path in exported_files(&dir, &format) {
+            if outputs.contains(&path) {
+                continue;
+            }
+            let action = if state.file(&config.root, &path).is_none() {
+                reporter.report(PullEvent::UnknownFileFound { path: path.clone() });
+                PlannedAction::Skip {
+                    path,
+                    reason: "not written by offshape".into(),
                 }
-                TranslationState::Done => {
-                    for j in jobs {
-                        let bytes = client
-                            .download_translated_file(&j, options.strip_indeterminism)?;
-
-                        let mut output_path: Utf8PathBuf =
-                            config.format_path(&j.format).unwrap().into();
-                        output_path.push(j.output_filename.clone());
-                        eprintln!("Writing translation to {}", j.output_filename);
-                        write_output_file(output_path, &bytes, options.strip_indeterminism)?;
-                    }
+            } else if options.should_clean_paths() {
+                PlannedAction::Delete { path }
+            } else {
+                PlannedAction::Skip {
+                    path,
+                    reason: "--no-clean-paths".into(),
                 }
-                TranslationState::Failed => {
-                    for j in jobs {
-                        let failure_reason = &(*j)
-                            .failure_reason
-                            .clone()
-                            .unwrap_or("Unknown reason".into());
-                        eprintln!("Translation failed: {}", failure_reason);
+            };
+            actions.push(action);
+        }
+    }
+
+    actions
+}
+
+/// Carries out a plan made by [`plan_pull`]
+pub async fn execute_plan(
+    client: &OnShapeClient,
+    plan: &PullPlan,
+    options: &PullOptions,
+    reporter: &dyn PullReporter,
+) -> Result<PullSummary> {
+    let config = &plan.config;
+    let document_id = &config.document.id;
+    let wvm = &config.document.wvm();
+
+    // Exports are written into a staging directory, and only moved into place once they've
+    // all succeeded. The staging directories are removed when this returns.
+    let _staging = Staging::create(config)?;
+
+    // Export every part in every format concurrently. The client's rate limiter keeps us
+    // within OnShape's request quota.
+    let mut exports = vec![];
+    for action in plan.actions.iter() {
+        let PlannedAction::Export {
+            output: output_path,
+            format: f,
+            studio_id,
+            part_id,
+            part_name,
+            microversion_id,
+        } = action
+        else {
+            continue;
+        };
+        exports.push(async move {
+            let bytes = match f.export_action() {
+                ExportAction::Direct => Bytes::from(
+                    client
+                        .get_part_stl(document_id, wvm, studio_id, part_id)
+                        .await?,
+                ),
+                ExportAction::Translate => {
+                    let job = client
+                        .begin_translation(
+                            f,
+                            document_id,
+                            wvm,
+                            studio_id,
+                            part_id,
+                            &part_basename(part_name),
+                        )
+                        .await?;
+                    reporter.report(PullEvent::JobSubmitted {
+                        output: output_path.clone(),
+                        job_id: job.id.clone(),
+                    });
+                    match await_translation(client, job, output_path, reporter).await? {
+                        Ok(job) => {
+                            client
+                                .download_translated_file(&job, options.strip_indeterminism)
+                                .await?
+                        }
+                        Err(failure_reason) => {
+                            reporter.report(PullEvent::Failure {
+                                output: output_path.clone(),
+                                reason: failure_reason.clone(),
+                            });
+                            return Ok(Err((output_path.clone(), failure_reason)));
+                        }
                     }
                 }
+            };
+
+            write_output_file(
+                &Staging::path(output_path),
+                &bytes,
+                options.strip_indeterminism,
+            )?;
+            reporter.report(PullEvent::FileExported {
+                output: output_path.clone(),
+                bytes: bytes.len(),
+            });
+            let record = FileRecord {
+                studio_id: studio_id.clone(),
+                part_id: part_id.clone(),
+                part_name: part_name.clone(),
+                format: *f,
+                microversion_id: microversion_id.clone(),
+            };
+            anyhow::Ok(Ok((output_path.clone(), record)))
+        });
+    }
+
+    // Thumbnails are rendered alongside the exports, and staged in the same way
+    let mut images = vec![];
+    for action in plan.actions.iter() {
+        let PlannedAction::Thumbnail {
+            output,
+            studio_id,
+            part_id,
+            size,
+        } = action
+        else {
+            continue;
+        };
+        images.push(async move {
+            let bytes = match part_id {
+                Some(part_id) => {
+                    client
+                        .get_part_shaded_view(document_id, wvm, studio_id, part_id, size)
+                        .await?
+                }
+                None => {
+                    client
+                        .get_element_thumbnail(document_id, wvm, studio_id, size)
+                        .await?
+                }
+            };
+            write_output_file(&Staging::path(output), &bytes, options.strip_indeterminism)?;
+            anyhow::Ok(output.clone())
+        });
+    }
+
+    let (results, images) = futures::try_join!(try_join_all(exports), try_join_all(images))?;
+    let (summary, written) = finish_pull(plan, results, images, options, reporter)?;
+    run_hooks(config, &written).await?;
+    Ok(summary)
+}
+
+/// Moves the files staged by [`execute_plan`] into place, removes stale files, and records
+/// the pull. Returns the summary of the pull, along with each file written and its record.
+fn finish_pull(
+    plan: &PullPlan,
+    results: Vec<ExportResult>,
+    images: Vec<Utf8PathBuf>,
+    options: &PullOptions,
+    reporter: &dyn PullReporter,
+) -> Result<(PullSummary, Vec<(Utf8PathBuf, FileRecord)>)> {
+    let config = &plan.config;
+    let SyncedDocument {
+        id: ref document_id,
+        ref workspace_id,
+        ref version,
+        ..
+    } = config.document;
+
+    let failure_count = results.iter().filter(|r| r.is_err()).count();
+    if failure_count > 0 && !options.allow_partial {
+        return Err(anyhow!(
+            "{failure_count} of {} exports failed, so no files were changed. Pass \
+             --allow-partial to keep the exports that succeeded.",
+            results.len()
+        ));
+    }
+
+    // Nothing on disk has changed up to this point. Files that failed to export keep their
+    // previous contents.
+    let written_paths = results.iter().flatten().map(|(path, _)| path.as_path());
+    let paths: Vec<&Utf8Path> = written_paths
+        .chain(images.iter().map(|p| p.as_path()))
+        .collect();
+    move_into_place(&paths, reporter)?;
+    let mut summary = PullSummary {
+        images,
+        ..Default::default()
+    };
+    // The pull is recorded even if a file can't be removed, so that the record matches
+    // the files that were written
+    let deleted = delete_stale_files(plan, reporter);
+
+    // Files from earlier pulls that weren't cleaned keep their records
+    let mut state = PullState::load(&config.root)?;
+    if state.document_id != *document_id || state.workspace_id != *workspace_id {
+        state = PullState {
+            document_id: document_id.clone(),
+            workspace_id: workspace_id.clone(),
+            ..Default::default()
+        };
+    }
+    state.version_id = version.as_ref().map(|v| v.id.clone());
+    state.version_name = version.as_ref().map(|v| v.name.clone());
+    state
+        .files
+        .retain(|path, _| config.root.join(path).exists());
+
+    let mut written = vec![];
+    for result in results {
+        match result {
+            Ok((path, record)) => {
+                state
+                    .files
+                    .insert(relative_path(&config.root, &path), record.clone());
+                summary.written.push(path.clone());
+                written.push((path, record));
+            }
+            Err(failure) => summary.failed.push(failure),
+        }
+    }
+    state.save(&config.root)?;
+
+    summary.deleted = deleted?;
+    Ok((summary, written))
+}
+
+/// Moves the staged copy of each of `paths` into place. The files they replace are kept in
+/// the staging directories until every file has been moved, so that if a move fails, the
+/// moves already made are undone, and the files are left as they were.
+fn move_into_place(paths: &[&Utf8Path], reporter: &dyn PullReporter) -> Result<()> {
+    let mut moved = vec![];
+    for path in paths.iter() {
+        match replace_with_staged(path) {
+            Ok(backup) => moved.push((path, backup)),
+            Err(e) => {
+                for (path, backup) in moved.into_iter().rev() {
+                    let _ = match backup {
+                        Some(backup) => rename(backup, path),
+                        None => remove_file(path),
+                    };
+                }
+                return Err(anyhow!(
+                    "Could not move {path} into place, so no files were changed: {e}"
+                ));
             }
         }
-        active_jobs = next;
     }
 
+    for path in paths.iter() {
+        reporter.report(PullEvent::FileWritten {
+            output: path.to_path_buf(),
+            bytes: path.metadata()?.len() as usize,
+        });
+    }
     Ok(())
 }
 
+/// Replaces `path` with its staged copy, returning where the file it replaced was moved to,
+/// if there was one
+fn replace_with_staged(path: &Utf8Path) -> io::Result<Option<Utf8PathBuf>> {
+    let backup = Staging::backup_path(path);
+    let backup = match rename(path, &backup) {
+        Ok(()) => Some(backup),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e),
+    };
+    if let Err(e) = rename(Staging::path(path), path) {
+        if let Some(backup) = backup {
+            let _ = rename(backup, path);
+        }
+        return Err(e);
+    }
+    Ok(backup)
+}
+
+/// Removes the files that `plan` deletes, reporting each directory that was cleaned. A file
+/// that's already gone counts as deleted.
+fn delete_stale_files(
+    plan: &PullPlan,
+    reporter: &dyn PullReporter,
+) -> Result<Vec<Utf8PathBuf>> {
+    let mut deleted = vec![];
+    for action in plan.actions.iter() {
+        let PlannedAction::Delete { path } = action else {
+            continue;
+        };
+        match remove_file(path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(anyhow!("Could not remove {path}: {e}")),
+        }
+        deleted.push(path.clone());
+    }
+    for dir in deleted.iter().filter_map(|p| p.parent()).unique() {
+        reporter.report(PullEvent::PathCleaned { path: dir.into() });
+    }
+    Ok(deleted)
+}
+
+/// Runs the config's hooks for a pull that wrote `written`
+async fn run_hooks(config: &SyncConfig, written: &[(Utf8PathBuf, FileRecord)]) -> Result<()> {
+    let Hooks {
+        ref after_file,
+        ref after_pull,
+    } = config.hooks;
+
+    if let Some(command) = after_file {
+        for (path, record) in written {
+            run_hook(
+                command,
+                &config.root,
+                &[
+                    ("OFFSHAPE_FILE", path.as_str()),
+                    ("OFFSHAPE_PART_NAME", &record.part_name),
+                    ("OFFSHAPE_PART_ID", &record.part_id),
+                    ("OFFSHAPE_FORMAT", &record.format.extension()),
+                ],
+            )
+            .await?;
+        }
+    }
+    if let Some(command) = after_pull {
+        let files = written.iter().map(|(path, _)| path.as_str()).join("\n");
+        run_hook(command, &config.root, &[("OFFSHAPE_FILES", &files)]).await?;
+    }
+    Ok(())
+}
+
+/// A directory within each output directory that exports are written into until the pull
+/// has succeeded. Being on the same filesystem as the output directory allows staged files
+/// to be renamed into place.
+struct Staging {
+    dirs: Vec<Utf8PathBuf>,
+}
+impl Staging {
+    const DIR_NAME: &'static str = ".offshape-staging";
+    const BACKUP_EXTENSION: &'static str = "offshape-old";
+
+    /// Creates the output directories, and empty staging directories within them
+    fn create(config: &SyncConfig) -> Result<Self> {
+        let mut output_dirs: Vec<Box<Utf8Path>> = config
+            .output_dirs()
+            .into_iter()
+            .map(|(_, dir)| dir)
+            .collect();
+        if let Some(ref thumbnails) = config.thumbnails {
+            output_dirs.push(thumbnails.path.clone());
+        }
+
+        let mut dirs = vec![];
+        for dir in output_dirs.into_iter().unique() {
+            let dir = dir.join(Self::DIR_NAME);
+            // Left behind by an interrupted pull, which may have moved only some of its
+            // files into place. The files it replaced are restored, so that they match the
+            // record of the last pull.
+            if dir.exists() {
+                for output in Self::restore_backups(&dir)? {
+                    log::warn!("Restored {output}, which an interrupted pull had replaced");
+                }
+                log::warn!(
+                    "Removing {dir}, which was left behind by an interrupted pull. The files \
+                     beside it may include new exports that it didn't record."
+                );
+                remove_dir_all(&dir)?;
+            }
+            create_dir_all(&dir)?;
+            dirs.push(dir);
+        }
+        Ok(Self { dirs })
+    }
+
+    /// Moves the files kept in `staging_dir` by an interrupted pull back to where they were,
+    /// returning their paths
+    fn restore_backups(staging_dir: &Utf8Path) -> Result<Vec<Utf8PathBuf>> {
+        let output_dir = staging_dir.parent().unwrap();
+        let mut restored = vec![];
+        for entry in staging_dir.read_dir_utf8()? {
+            let entry = entry?;
+            if entry.path().extension() != Some(Self::BACKUP_EXTENSION) {
+                continue;
+            }
+            // See `backup_path`, which adds the extension to the file's own
+            let name = entry.path().file_stem().unwrap();
+            let output = output_dir.join(name.strip_suffix('.').unwrap_or(name));
+            rename(entry.path(), &output).map_err(|e| {
+                anyhow!(
+                    "Could not restore {output} from {}, which was left behind by an \
+                     interrupted pull. Move it back into place before pulling again: {e}",
+                    entry.path()
+                )
+            })?;
+            restored.push(output);
+        }
+        Ok(restored)
+    }
+
+    /// Returns the path that `output` is staged at
+    fn path(output: &Utf8Path) -> Utf8PathBuf {
+        let mut path = output.with_file_name(Self::DIR_NAME);
+        path.push(output.file_name().unwrap());
+        path
+    }
+
+    /// Returns the path that the file previously at `output` is kept at, while the pull's
+    /// files are moved into place
+    fn backup_path(output: &Utf8Path) -> Utf8PathBuf {
+        let mut path = Self::path(output);
+        path.set_extension(format!(
+            "{}.{}",
+            output.extension().unwrap_or_default(),
+            Self::BACKUP_EXTENSION
+        ));
+        path
+    }
+}
+impl Drop for Staging {
+    fn drop(&mut self) {
+        for dir in self.dirs.iter() {
+            let _ = remove_dir_all(dir);
+        }
+    }
+}
+
+/// Prints each action in `plan`, as JSON lines if JSON reporting was requested
+fn print_plan(plan: &PullPlan, reporter: ReporterKind) -> Result<()> {
+    for action in plan.actions.iter() {
+        match reporter {
+            ReporterKind::Json => println!("{}", serde_json::to_string(action)?),
+            _ => println!("{action}"),
+        }
+    }
+    Ok(())
+}
+
+/// Polls a translation job until it completes, returning OnShape's failure reason if the
+/// translation failed.
+async fn await_translation(
+    client: &OnShapeClient,
+    mut job: TranslationJobWithOutput,
+    output_path: &Utf8Path,
+    reporter: &dyn PullReporter,
+) -> Result<std::result::Result<TranslationJobWithOutput, String>> {
+    loop {
+        let previous_state = job.request_state;
+        job = client.check_translation(&job).await?;
+        if job.request_state != previous_state {
+            reporter.report(PullEvent::JobStateChanged {
+                output: output_path.into(),
+                state: job.request_state,
+            });
+        }
+        match job.request_state {
+            TranslationState::Active => sleep(TRANSLATION_POLL_INTERVAL).await,
+            TranslationState::Done => return Ok(Ok(job)),
+            TranslationState::Failed => {
+                return Ok(Err(job
+                    .failure_reason
+                    .clone()
+                    .unwrap_or("Unknown reason".into())))
+            }
+        }
+    }
+}
+
+/// The name, without extension, of the files a part is exported to
+pub(crate) fn part_basename(part_name: &str) -> String {
+    part_name.to_case(Case::Snake)
+}
+
+/// The file a part is exported to, within the directory `dir`
+pub(crate) fn output_path(
+    dir: &Utf8Path,
+    format: &ExportFileFormat,
+    basename: &str,
+) -> Utf8PathBuf {
+    let mut path: Utf8PathBuf = dir.into();
+    path.push(format!("{basename}.{ext}", ext = format.extension()));
+    path
+}
+
+/// The image of a part or studio, rendered at `size`, within the directory `dir`
+fn thumbnail_path(dir: &Utf8Path, basename: &str, size: &ThumbnailSize) -> Utf8PathBuf {
+    dir.join(format!("{basename}-{size}.png"))
+}
+
 fn write_output_file(
-    output_path: Utf8PathBuf,
+    output_path: &Utf8Path,
     bytes: &[u8],
     strip_timestamps: bool,
 ) -> anyhow::Result<()> {
     let mut f = File::create(output_path)?;
-    f.write(bytes)?;
+    f.write_all(bytes)?;
     if strip_timestamps {
         f.set_modified(SystemTime::UNIX_EPOCH)?;
     }
@@ -202,19 +855,357 @@ fn write_output_file(
     Ok(())
 }
 
-fn clean_path(path: &camino::Utf8Path, ext: &str) {
-    eprintln!("Cleaning {}", path);
+/// Writes `bytes` to a temporary file beside `path`, then renames it into place, so that
+/// readers (and interrupted pulls) never see a partially written file. `finish` is called
+/// with the temporary file before it is renamed.
+pub(crate) fn write_atomically(
+    path: &Utf8Path,
+    bytes: &[u8],
+    finish: impl FnOnce(&File) -> io::Result<()>,
+) -> io::Result<()> {
+    let temp_path = path.with_file_name(format!(
+        ".{}.offshape-tmp",
+        path.file_name().unwrap_or_default()
+    ));
+    let res = File::create(&temp_path).and_then(|mut f| {
+        f.write_all(bytes)?;
+        finish(&f)?;
+        f.sync_all()
+    });
+    match res.and_then(|_| rename(&temp_path, path)) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            let _ = remove_file(&temp_path);
+            Err(e)
+        }
+    }
+}
+
+/// Lists the files in `dir` that have `format`'s extension
+pub(crate) fn exported_files(dir: &Utf8Path, format: &ExportFileFormat) -> Vec<Utf8PathBuf> {
+    let Ok(entries) = dir.read_dir_utf8() else {
+        return vec![];
+    };
+    let mut files: Vec<Utf8PathBuf> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.into_path())
+        .filter(|p| {
+            p.extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case(&format.extension()))
+        })
+        .filter(|p| p.is_file())
+        .collect();
+    files.sort();
+    files
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use indoc::indoc;
+
+    use super::*;
+    use crate::{test_fixtures::TempRoot, NullReporter};
+
+    fn config(root: &Utf8Path, source: &str) -> SyncConfig {
+        let mut config: SyncConfig = toml::from_str(source).unwrap();
+        for path in [
+            &mut config.three_mf_path,
+            &mut config.step_path,
+            &mut config.stl_path,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            *path = root.join(&**path).into();
+        }
+        config.root = root.into();
+        config
+    }
+
+    fn record(id: &str, name: &str) -> FileRecord {
+        FileRecord {
+            studio_id: "59628be740b2cf41a42e4c8a".into(),
+            part_id: id.into(),
+            part_name: name.into(),
+            format: ExportFileFormat::Stl,
+            microversion_id: "mv1".into(),
+        }
+    }
+
+    #[test]
+    fn test_plan_actions() {
+        let root = TempRoot::new();
+        let config = config(
+            &root,
+            indoc! {r#"
+                stl_path = "stl"
+
+                [document]
+                id = "6a1de9567f16cd7d1f564dbb"
+                workspace_id = "da2053ce82593768f41daab4"
+
+                [[part_studio]]
+                id = "59628be740b2cf41a42e4c8a"
+            "#},
+        );
+        let stl_dir = root.join("stl");
+        fs::create_dir_all(&stl_dir).unwrap();
+        for name in ["left_bracket", "old_part", "hand_made"] {
+            fs::write(stl_dir.join(format!("{name}.stl")), "solid").unwrap();
+        }
+        let state = PullState {
+            document_id: config.document.id.clone(),
+            workspace_id: config.document.workspace_id.clone(),
+            files: [
+                ("stl/left_bracket.stl".into(), record("JHD", "Left Bracket")),
+                ("stl/old_part.stl".into(), record("JHL", "Old Part")),
+            ]
+            .into(),
+            ..Default::default()
+        };
+        let parts = [vec![Part {
+            name: "Left Bracket".into(),
+            part_id: "JHD".into(),
+            element_id: "59628be740b2cf41a42e4c8a".into(),
+            microversion_id: "mv2".into(),
+            body_type: "solid".into(),
+        }]];
+        let describe = |actions: Vec<PlannedAction>| -> Vec<String> {
+            actions
+                .iter()
+                .map(|a| a.to_string().replace(root.as_str(), ""))
+                .collect()
+        };
+
+        // Only files in the manifest are deleted
+        let actions = plan_actions(
+            &config,
+            &state,
+            &parts,
+            &PullOptions::default(),
+            &NullReporter,
+        );
+        assert_eq!(
+            describe(actions),
+            [
+                "export  /stl/left_bracket.stl (Left Bracket)",
+                "skip    /stl/hand_made.stl (not written by offshape)",
+                "delete  /stl/old_part.stl",
+            ]
+        );
+
+        let options = PullOptions {
+            no_clean_paths: true,
+            ..Default::default()
+        };
+        let actions = plan_actions(&config, &state, &parts, &options, &NullReporter);
+        assert_eq!(
+            describe(actions),
+            [
+                "export  /stl/left_bracket.stl (Left Bracket)",
+                "skip    /stl/hand_made.stl (not written by offshape)",
+                "skip    /stl/old_part.stl (--no-clean-paths)",
+            ]
+        );
+
+        // Planning, which is all that a dry run does, leaves every file in place
+        let remaining = exported_files(&stl_dir, &ExportFileFormat::Stl);
+        assert_eq!(remaining.len(), 3);
+    }
+
+    #[test]
+    fn test_delete_stale_files_in_shared_directory() {
+        let root = TempRoot::new();
+        let config = config(
+            &root,
+            indoc! {r#"
+                step_path = "export"
+                stl_path = "export"
 
-    let entries = std::fs::read_dir(path).expect("Could not read path");
-    for res in entries {
-        let entry = match res {
-            Ok(entry) => entry,
-            Err(e) => panic!("{}", e),
+                [document]
+                id = "6a1de9567f16cd7d1f564dbb"
+                workspace_id = "da2053ce82593768f41daab4"
+
+                [[part_studio]]
+                id = "59628be740b2cf41a42e4c8a"
+            "#},
+        );
+        let dir = root.join("export");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("old_part.stl"), "solid").unwrap();
+        fs::write(dir.join("old_part.step"), "ISO-10303-21;").unwrap();
+        let plan = PullPlan {
+            config,
+            actions: vec![
+                PlannedAction::Delete {
+                    path: dir.join("old_part.stl"),
+                },
+                PlannedAction::Delete {
+                    path: dir.join("old_part.step"),
+                },
+                // Already removed by someone else
+                PlannedAction::Delete {
+                    path: dir.join("gone.stl"),
+                },
+            ],
         };
 
-        let name = entry.file_name().into_string().expect("");
-        if name.ends_with(ext) {
-            std::fs::remove_file(entry.path()).expect("Could not delete file");
+        let deleted = delete_stale_files(&plan, &NullReporter);
+        let remaining = dir.read_dir_utf8().unwrap().count();
+        assert_eq!(deleted.unwrap().len(), 3);
+        assert_eq!(remaining, 0);
+    }
+
+    #[test]
+    fn test_failed_move_restores_replaced_files() {
+        let root = TempRoot::new();
+        let config = config(
+            &root,
+            indoc! {r#"
+                stl_path = "stl"
+
+                [document]
+                id = "6a1de9567f16cd7d1f564dbb"
+                workspace_id = "da2053ce82593768f41daab4"
+
+                [[part_studio]]
+                id = "59628be740b2cf41a42e4c8a"
+            "#},
+        );
+        let _staging = Staging::create(&config).unwrap();
+        let stl_dir = root.join("stl");
+        let (left, knob, new) = (
+            stl_dir.join("left_bracket.stl"),
+            stl_dir.join("knob.stl"),
+            stl_dir.join("new_part.stl"),
+        );
+        fs::write(&left, "old left").unwrap();
+        fs::write(&knob, "old knob").unwrap();
+        fs::write(Staging::path(&left), "new left").unwrap();
+        fs::write(Staging::path(&new), "new part").unwrap();
+        // The knob was never staged, so it can't be moved into place
+
+        let res = move_into_place(&[&left, &new, &knob], &NullReporter);
+        let contents = [&left, &knob].map(|p| fs::read_to_string(p).unwrap());
+        let new_exists = new.exists();
+
+        assert!(res.is_err());
+        assert_eq!(contents, ["old left", "old knob"]);
+        assert!(!new_exists);
+    }
+
+    #[test]
+    fn test_interrupted_move_is_restored() {
+        let root = TempRoot::new();
+        let config = config(
+            &root,
+            indoc! {r#"
+                stl_path = "stl"
+
+                [document]
+                id = "6a1de9567f16cd7d1f564dbb"
+                workspace_id = "da2053ce82593768f41daab4"
+
+                [[part_studio]]
+                id = "59628be740b2cf41a42e4c8a"
+            "#},
+        );
+        let stl_dir = root.join("stl");
+        let (left, knob) = (stl_dir.join("left_bracket.stl"), stl_dir.join("knob.stl"));
+        // The pull was interrupted after moving the new left bracket into place, and while
+        // the knob had been moved aside
+        drop(Staging::create(&config).unwrap());
+        let staging_dir = stl_dir.join(Staging::DIR_NAME);
+        fs::create_dir_all(&staging_dir).unwrap();
+        fs::write(&left, "new left").unwrap();
+        fs::write(Staging::backup_path(&left), "old left").unwrap();
+        fs::write(Staging::backup_path(&knob), "old knob").unwrap();
+        fs::write(Staging::path(&knob), "new knob").unwrap();
+
+        let _staging = Staging::create(&config).unwrap();
+        let contents = [&left, &knob].map(|p| fs::read_to_string(p).unwrap());
+        let staged = fs::read_dir(&staging_dir).unwrap().count();
+
+        assert_eq!(contents, ["old left", "old knob"]);
+        assert_eq!(staged, 0);
+    }
+
+    #[test]
+    fn test_finish_partial_pull() {
+        let root = TempRoot::new();
+        let config = config(
+            &root,
+            indoc! {r#"
+                stl_path = "stl"
+
+                [document]
+                id = "6a1de9567f16cd7d1f564dbb"
+                workspace_id = "da2053ce82593768f41daab4"
+
+                [[part_studio]]
+                id = "59628be740b2cf41a42e4c8a"
+            "#},
+        );
+        let _staging = Staging::create(&config).unwrap();
+        let stl_dir = root.join("stl");
+        let (left, knob) = (stl_dir.join("left_bracket.stl"), stl_dir.join("knob.stl"));
+        fs::write(&knob, "old knob").unwrap();
+        PullState {
+            document_id: config.document.id.clone(),
+            workspace_id: config.document.workspace_id.clone(),
+            files: [("stl/knob.stl".into(), record("JHH", "Knob"))].into(),
+            ..Default::default()
         }
+        .save(&root)
+        .unwrap();
+        fs::write(Staging::path(&left), "new left").unwrap();
+
+        let plan = PullPlan {
+            config,
+            actions: vec![],
+        };
+        let results = || -> Vec<ExportResult> {
+            vec![
+                Ok((left.clone(), record("JHD", "Left Bracket"))),
+                Err((knob.clone(), "Translation failed".into())),
+            ]
+        };
+
+        // By default, one failure leaves everything unchanged
+        let res = finish_pull(
+            &plan,
+            results(),
+            vec![],
+            &PullOptions::default(),
+            &NullReporter,
+        );
+        assert!(res.is_err());
+        assert!(!left.exists());
+        assert_eq!(PullState::load(&root).unwrap().files.len(), 1);
+
+        let options = PullOptions {
+            allow_partial: true,
+            ..Default::default()
+        };
+        let (summary, written) =
+            finish_pull(&plan, results(), vec![], &options, &NullReporter).unwrap();
+        let contents = [&left, &knob].map(|p| fs::read_to_string(p).unwrap());
+        let state = PullState::load(&root).unwrap();
+
+        assert_eq!(summary.written, [left]);
+        assert_eq!(summary.failed.len(), 1);
+        assert_eq!(written.len(), 1);
+        // The failed export keeps its previous contents, and its record
+        assert_eq!(contents, ["new left", "old knob"]);
+        assert_eq!(
+            state.files.keys().map(|p| p.as_str()).collect::<Vec<_>>(),
+            ["stl/knob.stl", "stl/left_bracket.stl"]
+        );
+        assert_eq!(
+            state.files[Utf8Path::new("stl/knob.stl")],
+            record("JHH", "Knob")
+        );
     }
 }
@@ -0,0 +1,59 @@
+use anyhow::Result;
+use clap::Args;
+
+use crate::{
+    config::{SyncConfig, SyncedDocument},
+    onshape::environment_client,
+    pull::{pull, PullOptions},
+    report::ReporterKind,
+    GlobalOptions,
+};
+
+#[derive(Args, Debug)]
+pub struct ReleaseOptions {
+    /// The name of the version to create, like "v1.4"
+    #[arg(long)]
+    pub name: String,
+    /// A description of the version
+    #[arg(long)]
+    pub description: Option<String>,
+    /// How progress is reported while pulling
+    #[arg(long, default_value_t = ReporterKind::Auto)]
+    pub reporter: ReporterKind,
+}
+
+/// Creates a named version of the configured workspace, then pulls from that version, so
+/// the exported files correspond to an immutable state of the document
+pub async fn release(
+    mut config: SyncConfig,
+    global_options: GlobalOptions,
+    options: ReleaseOptions,
+) -> Result<()> {
+    let client = environment_client(&global_options, config.base_url.as_deref())?;
+    let SyncedDocument {
+        ref id,
+        ref workspace_id,
+        ..
+    } = config.document;
+
+    let version = client
+        .create_version(
+            id,
+            workspace_id,
+            &options.name,
+            options.description.as_deref(),
+        )
+        .await?;
+    log::info!("Created version {} ({})", version.name, version.id);
+    config.document.version = Some(version);
+
+    let pull_options = PullOptions {
+        reporter: options.reporter,
+        ..Default::default()
+    };
+    let reporter = options.reporter.reporter();
+    let res = pull(&client, &config, &pull_options, reporter.as_ref()).await;
+    reporter.finish();
+    res?;
+    Ok(())
+}
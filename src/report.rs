@@ -0,0 +1,224 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    io::{stderr, stdout, IsTerminal, Write},
+    time::Duration,
+};
+
+use camino::Utf8PathBuf;
+use clap::ValueEnum;
+use indicatif::{ProgressBar, ProgressStyle};
+use serde::Serialize;
+
+use crate::onshape::{models::TranslationState, ElementId, PartId};
+
+/// Something noteworthy that happened during a pull
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum PullEvent {
+    /// A configured part studio was found in the document, and its parts listed
+    StudioResolved {
+        studio_id: ElementId,
+        display_name: String,
+        part_count: usize,
+    },
+    /// A part that will be exported, along with the files it will be written to
+    PartDiscovered {
+        studio_id: ElementId,
+        part_id: PartId,
+        name: String,
+        outputs: Vec<Utf8PathBuf>,
+    },
+    /// Previously exported files were removed from an output directory
+    PathCleaned { path: Utf8PathBuf },
+    /// A file in an output directory wasn't written by offshape, so it was left alone
+    UnknownFileFound { path: Utf8PathBuf },
+    /// OnShape accepted a request to translate a part into `output`'s format
+    JobSubmitted { output: Utf8PathBuf, job_id: String },
+    /// A translation job moved into a new state
+    JobStateChanged {
+        output: Utf8PathBuf,
+        state: TranslationState,
+    },
+    /// A file was exported into the staging directory
+    FileExported { output: Utf8PathBuf, bytes: usize },
+    /// An exported file was moved into place
+    FileWritten { output: Utf8PathBuf, bytes: usize },
+    /// A file could not be exported
+    Failure { output: Utf8PathBuf, reason: String },
+}
+
+impl Display for PullEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            PullEvent::StudioResolved {
+                display_name,
+                part_count,
+                ..
+            } => write!(f, "Found {part_count} parts in {display_name}"),
+            PullEvent::PartDiscovered { name, outputs, .. } => {
+                write!(f, "Exporting {name} to {} files", outputs.len())
+            }
+            PullEvent::PathCleaned { path } => write!(f, "Cleaned {path}"),
+            PullEvent::UnknownFileFound { path } => write!(
+                f,
+                "Leaving {path} in place, as it wasn't written by offshape"
+            ),
+            PullEvent::JobSubmitted { output, .. } => {
+                write!(f, "Translating {}", output.file_name().unwrap_or_default())
+            }
+            PullEvent::JobStateChanged { output, state } => write!(
+                f,
+                "Translation of {} is {state:?}",
+                output.file_name().unwrap_or_default()
+            ),
+            PullEvent::FileExported { output, .. } => {
+                write!(f, "Exported {}", output.file_name().unwrap_or_default())
+            }
+            PullEvent::FileWritten { output, .. } => write!(f, "Wrote {output}"),
+            PullEvent::Failure { output, reason } => {
+                write!(f, "Failed to export {output}: {reason}")
+            }
+        }
+    }
+}
+
+/// Receives the events emitted during a pull
+pub trait PullReporter: Sync {
+    fn report(&self, event: PullEvent);
+
+    /// Called once the pull has finished, successfully or not
+    fn finish(&self) {}
+}
+
+/// Discards every event
+pub struct NullReporter;
+impl PullReporter for NullReporter {
+    fn report(&self, _event: PullEvent) {}
+}
+
+/// Logs a line describing each event, reporting failures as warnings
+pub struct LogReporter;
+impl PullReporter for LogReporter {
+    fn report(&self, event: PullEvent) {
+        match event {
+            PullEvent::Failure { .. } | PullEvent::UnknownFileFound { .. } => {
+                log::warn!("{event}")
+            }
+            PullEvent::JobStateChanged { .. } | PullEvent::FileExported { .. } => {
+                log::debug!("{event}")
+            }
+            _ => log::info!("{event}"),
+        }
+    }
+}
+
+/// Writes each event to stdout as a line of JSON, for consumption by other tools
+pub struct JsonReporter;
+impl PullReporter for JsonReporter {
+    fn report(&self, event: PullEvent) {
+        let line = serde_json::to_string(&event).expect("Events are always serializable");
+        let mut out = stdout().lock();
+        // A closed stdout isn't worth failing the pull over
+        let _ = writeln!(out, "{line}");
+        let _ = out.flush();
+    }
+}
+
+/// Draws a progress bar on the terminal, counting the files that have been exported
+pub struct ProgressBarReporter {
+    bar: ProgressBar,
+}
+impl ProgressBarReporter {
+    pub fn new() -> Self {
+        let bar = ProgressBar::new(0).with_style(
+            ProgressStyle::with_template("{spinner} [{bar:30}] {pos}/{len} {wide_msg}")
+                .unwrap()
+                .progress_chars("=> "),
+        );
+        bar.enable_steady_tick(Duration::from_millis(100));
+        Self { bar }
+    }
+}
+impl Default for ProgressBarReporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl PullReporter for ProgressBarReporter {
+    fn report(&self, event: PullEvent) {
+        match event {
+            PullEvent::PartDiscovered { ref outputs, .. } => {
+                self.bar.inc_length(outputs.len() as u64);
+            }
+            PullEvent::FileExported { .. } => self.bar.inc(1),
+            PullEvent::UnknownFileFound { .. } => {
+                self.bar.println(event.to_string());
+                return;
+            }
+            PullEvent::Failure { .. } => {
+                self.bar.println(event.to_string());
+                self.bar.inc(1);
+                return;
+            }
+            _ => {}
+        }
+        self.bar.set_message(event.to_string());
+    }
+
+    fn finish(&self) {
+        self.bar.finish_and_clear();
+    }
+}
+
+/// The built-in reporters selectable from the command line
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ReporterKind {
+    /// A progress bar, if stderr is a terminal, and plain logs otherwise
+    Auto,
+    Bar,
+    Log,
+    Json,
+    Quiet,
+}
+impl ReporterKind {
+    pub fn reporter(self) -> Box<dyn PullReporter> {
+        match self {
+            ReporterKind::Auto if stderr().is_terminal() => {
+                Box::new(ProgressBarReporter::new())
+            }
+            ReporterKind::Auto => Box::new(LogReporter),
+            ReporterKind::Bar => Box::new(ProgressBarReporter::new()),
+            ReporterKind::Log => Box::new(LogReporter),
+            ReporterKind::Json => Box::new(JsonReporter),
+            ReporterKind::Quiet => Box::new(NullReporter),
+        }
+    }
+}
+impl Display for ReporterKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ReporterKind::Auto => "auto",
+            ReporterKind::Bar => "bar",
+            ReporterKind::Log => "log",
+            ReporterKind::Json => "json",
+            ReporterKind::Quiet => "quiet",
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_events_serialize_as_tagged_json() {
+        let event = PullEvent::FileWritten {
+            output: "export/stl/bracket.stl".into(),
+            bytes: 1024,
+        };
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"event":"file_written","output":"export/stl/bracket.stl","bytes":1024}"#
+        );
+    }
+}
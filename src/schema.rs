@@ -0,0 +1,148 @@
+use anyhow::Result;
+use schemars::{
+    gen::{SchemaGenerator, SchemaSettings},
+    schema::Schema,
+};
+use serde_json::{json, Value};
+
+use crate::SyncConfig;
+
+/// Prints a JSON Schema describing offshape.toml, for editors and tools that generate
+/// configs
+pub fn schema() -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(&config_schema())?);
+    Ok(())
+}
+
+/// A JSON Schema describing [`SyncConfig`], generated from its type. Every object in it
+/// lists all of its properties, as `offshape check` reports any key it doesn't list as
+/// unknown.
+pub(crate) fn config_schema() -> Value {
+    let generator = SchemaSettings::draft07()
+        .with(|s| {
+            // Nested objects are written out in place, rather than as references, so that
+            // `check` can walk the schema alongside the config
+            s.inline_subschemas = true;
+            s.option_add_null_type = false;
+        })
+        .into_generator();
+    let mut schema = serde_json::to_value(generator.into_root_schema_for::<SyncConfig>())
+        .expect("Schemas are always serializable");
+
+    schema["title"] = "offshape.toml".into();
+    // Included files are merged before the config is deserialized, so `include` isn't one
+    // of its fields
+    schema["properties"]["include"] = json!({
+        "type": "array",
+        "description": "Configs to include, relative to this file. This file's settings take \
+                        precedence over theirs. Paths set in an included config are relative \
+                        to that config.",
+        "items": { "type": "string" },
+    });
+    tidy(&mut schema);
+    schema
+}
+
+/// Marks every object in `schema` as having no properties besides those it lists, and
+/// removes the null defaults given to optional fields
+fn tidy(schema: &mut Value) {
+    match schema {
+        Value::Object(object) => {
+            if object.contains_key("properties") {
+                object.insert("additionalProperties".into(), false.into());
+            }
+            if object.get("default") == Some(&Value::Null) {
+                object.remove("default");
+            }
+            object.values_mut().for_each(tidy);
+        }
+        Value::Array(items) => items.iter_mut().for_each(tidy),
+        _ => {}
+    }
+}
+
+fn schema_from(value: Value) -> Schema {
+    serde_json::from_value(value).expect("Schemas are valid")
+}
+
+/// The schema of [`crate::SyncedPartStudio::formats`], which are named by their extensions
+pub(crate) fn export_formats(_: &mut SchemaGenerator) -> Schema {
+    schema_from(json!({
+        "type": "array",
+        "items": { "enum": ["3mf", "step", "stl"] },
+    }))
+}
+
+/// The schema of [`crate::Thumbnails::sizes`]
+pub(crate) fn thumbnail_sizes(_: &mut SchemaGenerator) -> Schema {
+    schema_from(json!({
+        "type": "array",
+        "items": { "type": "string", "pattern": "^[0-9]+x[0-9]+$" },
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use indoc::indoc;
+
+    use super::*;
+
+    /// Collects the path of every property in `schema`
+    fn property_paths(schema: &Value, prefix: &str, paths: &mut Vec<String>) {
+        let schema = schema.get("items").unwrap_or(schema);
+        for (name, property) in schema["properties"].as_object().into_iter().flatten() {
+            let path = format!("{prefix}{name}");
+            property_paths(property, &format!("{path}."), paths);
+            paths.push(path);
+        }
+    }
+
+    #[test]
+    fn test_schema_describes_config() {
+        let config = indoc! {r#"
+            include = []
+            3mf_path = "3mf"
+            step_path = "step"
+            stl_path = "stl"
+            base_url = "https://cad.onshape.com/api"
+
+            [document]
+            id = "6a1de9567f16cd7d1f564dbb"
+            workspace_id = "da2053ce82593768f41daab4"
+            version = "v1.0"
+
+            [[part_studio]]
+            display_name = "Printables"
+            id = "59628be740b2cf41a42e4c8a"
+            name = "Printables"
+            formats = ["3mf", "stl"]
+            3mf_path = "printables/3mf"
+            step_path = "printables/step"
+            stl_path = "printables/stl"
+
+            [hooks]
+            after_file = "echo $OFFSHAPE_FILE"
+            after_pull = "echo done"
+
+            [thumbnails]
+            path = "images"
+            sizes = ["300x300", "1200x900"]
+        "#};
+        toml::from_str::<SyncConfig>(config).unwrap();
+
+        // Each of the schema's properties is set above
+        let value =
+            serde_json::to_value(toml::from_str::<toml::Table>(config).unwrap()).unwrap();
+        let mut paths = vec![];
+        property_paths(&config_schema(), "", &mut paths);
+        for path in paths {
+            let mut node = &value;
+            for segment in path.split('.') {
+                node = node.get(0).unwrap_or(node);
+                node = node
+                    .get(segment)
+                    .unwrap_or_else(|| panic!("{path} is missing"));
+            }
+        }
+    }
+}
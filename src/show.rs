@@ -1,11 +1,23 @@
+use std::{
+    collections::HashSet,
+    fmt::{self, Display, Formatter},
+};
+
 use anyhow::Result;
 use clap::{Args, ValueEnum};
-use convert_case::{Case, Casing};
-use indoc::printdoc;
+use itertools::Itertools;
+use serde::Serialize;
+use serde_json::json;
 
 use crate::{
-    config::{SyncConfig, SyncedDocument},
-    onshape::{environment_client, models::Part},
+    config::{SyncConfig, SyncedPartStudio},
+    onshape::{
+        environment_client,
+        models::{Document, Part, TabElementType},
+        ElementId, OnShapeClient,
+    },
+    pull::part_basename,
+    table::Table,
     GlobalOptions,
 };
 
@@ -22,88 +34,351 @@ impl Default for ShowPartsOptions {
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+#[derive(Args, Debug, Default)]
+pub struct ShowElementsOptions {
+    #[clap(long, short, default_value_t = OutputFormat::default())]
+    pub format: OutputFormat,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
 pub enum OutputFormat {
+    #[default]
     Friendly,
     Json,
+    /// Aligned columns, with a header
+    Table,
+    Csv,
 }
 
-impl Default for OutputFormat {
-    fn default() -> Self {
-        OutputFormat::Friendly
-    }
-}
-
-impl ToString for OutputFormat {
-    fn to_string(&self) -> String {
-        match self {
+impl Display for OutputFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
             OutputFormat::Friendly => "friendly",
             OutputFormat::Json => "json",
-        }
-        .into()
+            OutputFormat::Table => "table",
+            OutputFormat::Csv => "csv",
+        })
     }
 }
 
-pub fn show_parts(
+/// Prints the parts found in each configured part studio
+pub async fn show_parts(
     config: SyncConfig,
     global_options: GlobalOptions,
     options: ShowPartsOptions,
 ) -> Result<()> {
-    // Load the manifest describing what to sync
-    let SyncConfig {
-        document:
-            SyncedDocument {
-                id: document_id,
-                workspace_id,
-            },
-        part_studios,
-        ..
-    } = config;
+    let client = environment_client(&global_options, config.base_url.as_deref())?;
+    let config = config.resolved(&client).await?;
+    let document_id = &config.document.id;
+    let wvm = config.document.wvm();
+    let document = client.get_document(document_id).await?;
 
-    let client = environment_client(global_options.proxy_url)?;
-    let element_map = client.get_document_elements(&document_id, &workspace_id)?;
-
-    for sync_part_studio in part_studios {
-        if !element_map.contains_key(&sync_part_studio.id) {
-            panic!("Could not find an part_studio ({})", sync_part_studio.id);
+    if options.format == OutputFormat::Json {
+        // The API's JSON is passed through, so that no fields are lost
+        let mut part_studios = vec![];
+        for studio in config.part_studios.iter() {
+            let parts: serde_json::Value = serde_json::from_str(
+                &client
+                    .get_studio_parts_json(document_id, &wvm, &studio.id)
+                    .await?,
+            )?;
+            part_studios.push(json!({
+                "id": studio.id,
+                "display_name": studio.display_name,
+                "parts": parts,
+            }));
         }
+        let output = json!({
+            "document": { "id": document.id, "name": document.name },
+            "part_studios": part_studios,
+        });
+        println!("{}", serde_json::to_string_pretty(&output)?);
+        return Ok(());
+    }
+
+    let mut studios = vec![];
+    for studio in config.part_studios.iter() {
+        let parts = client
+            .get_studio_parts(document_id, &wvm, &studio.id)
+            .await?;
+        studios.push((studio.clone(), parts));
+    }
 
-        match options.format {
-            OutputFormat::Friendly => {
-                println!("PART_STUDIO {}\n", sync_part_studio.display_name);
-
-                let studio_parts = client.get_studio_parts(
-                    &document_id,
-                    &workspace_id,
-                    &sync_part_studio.id,
-                )?;
-                for part in studio_parts {
-                    let Part {
-                        ref name,
-                        ref part_id,
-                        ..
-                    } = part;
-                    let basename = name.to_case(Case::Snake);
-                    println!("PART {name}");
-                    println!("{:#?}", part);
-                    printdoc! {"
-                        offshape.toml entry:
-                        # {name}
-                        {{ id = \"{part_id}\", basename = \"{basename}\"}},
-
-                    "};
+    match options.format {
+        OutputFormat::Friendly => print!("{}", render_tree(&document, &studios)),
+        format => {
+            let mut table = Table::new(vec![
+                "studio_id",
+                "studio",
+                "part_id",
+                "part",
+                "body_type",
+                "basename",
+            ]);
+            for (studio, parts) in studios.iter() {
+                for part in parts {
+                    table.push(vec![
+                        studio.id.to_string(),
+                        studio.display_name.clone(),
+                        part.part_id.to_string(),
+                        part.name.clone(),
+                        part.body_type.clone(),
+                        part_basename(&part.name),
+                    ]);
                 }
             }
-            OutputFormat::Json => {
-                let json = client.get_studio_parts_json(
-                    &document_id,
-                    &workspace_id,
-                    &sync_part_studio.id,
-                )?;
-                println!("{}", json);
+            print!("{}", table.render(format));
+        }
+    }
+    Ok(())
+}
+
+/// Draws the document's studios and their parts as a tree, with each part's ID, body type
+/// and the basename of the files it's exported to
+fn render_tree(document: &Document, studios: &[(SyncedPartStudio, Vec<Part>)]) -> String {
+    let mut out = format!("{} ({})\n", document.name, document.id);
+    for (i, (studio, parts)) in studios.iter().enumerate() {
+        let last_studio = i == studios.len() - 1;
+        let (branch, indent) = if last_studio {
+            ("└── ", "    ")
+        } else {
+            ("├── ", "│   ")
+        };
+        out += &format!("{branch}{} ({})\n", studio.display_name, studio.id);
+        if parts.is_empty() {
+            out += &format!("{indent}└── (no parts)\n");
+            continue;
+        }
+
+        let width = |f: fn(&Part) -> usize| parts.iter().map(f).max().unwrap_or_default();
+        let name_width = width(|p| p.name.chars().count());
+        let id_width = width(|p| p.part_id.chars().count());
+        let body_width = width(|p| p.body_type.chars().count());
+        for (j, part) in parts.iter().enumerate() {
+            let branch = if j == parts.len() - 1 {
+                "└── "
+            } else {
+                "├── "
+            };
+            out += &format!(
+                "{indent}{branch}{:<name_width$}  {:<id_width$}  {:<body_width$}  {}\n",
+                part.name,
+                part.part_id.to_string(),
+                part.body_type,
+                part_basename(&part.name),
+            );
+        }
+    }
+    out
+}
+
+/// A tab in the document, as printed by [`show_elements`]
+#[derive(Clone, Debug, Serialize)]
+struct ElementEntry<'a> {
+    #[serde(rename = "type")]
+    element_type: TabElementType,
+    id: &'a ElementId,
+    name: &'a str,
+    /// Whether the tab is one of offshape.toml's part studios
+    synced: bool,
+}
+
+/// Prints every tab in the document, grouped by type, noting which are synced
+pub async fn show_elements(
+    mut config: SyncConfig,
+    global_options: GlobalOptions,
+    options: ShowElementsOptions,
+) -> Result<()> {
+    let client = environment_client(&global_options, config.base_url.as_deref())?;
+    if config.document.needs_version() {
+        let versions = client.get_document_versions(&config.document.id).await?;
+        config.document.resolve_version(&versions)?;
+    }
+    let elements = client
+        .get_document_elements(&config.document.id, config.document.wvm())
+        .await?;
+    config.resolve_part_studios(&elements)?;
+    let synced: HashSet<&ElementId> = config.part_studios.iter().map(|s| &s.id).collect();
+
+    let entries: Vec<ElementEntry> = elements
+        .values()
+        .sorted_by(|a, b| {
+            (a.element_type, &a.name, &a.id).cmp(&(b.element_type, &b.name, &b.id))
+        })
+        .map(|e| ElementEntry {
+            element_type: e.element_type,
+            id: &e.id,
+            name: &e.name,
+            synced: synced.contains(&e.id),
+        })
+        .collect();
+
+    match options.format {
+        OutputFormat::Friendly => print!("{}", render_elements(&entries)),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&entries)?),
+        format => {
+            let mut table = Table::new(vec!["type", "id", "name", "synced"]);
+            for entry in entries.iter() {
+                table.push(vec![
+                    element_type_name(entry.element_type).into(),
+                    entry.id.to_string(),
+                    entry.name.into(),
+                    entry.synced.to_string(),
+                ]);
             }
+            print!("{}", table.render(format));
+        }
+    }
+    Ok(())
+}
+
+/// Lists the entries under a heading for each type, marking those that are synced
+fn render_elements(entries: &[ElementEntry]) -> String {
+    let mut out = String::new();
+    for (element_type, group) in entries.iter().group_by(|e| e.element_type).into_iter() {
+        let group: Vec<&ElementEntry> = group.collect();
+        if !out.is_empty() {
+            out += "\n";
         }
+        out += &format!("{}:\n", element_type_name(element_type));
+        let name_width = group
+            .iter()
+            .map(|e| e.name.chars().count())
+            .max()
+            .unwrap_or_default();
+        for entry in group {
+            let line = format!(
+                "  {:<name_width$}  {}  {}",
+                entry.name,
+                entry.id,
+                if entry.synced { "(synced)" } else { "" }
+            );
+            out += &format!("{}\n", line.trim_end());
+        }
+    }
+    out
+}
+
+fn element_type_name(element_type: TabElementType) -> &'static str {
+    match element_type {
+        TabElementType::Application => "Applications",
+        TabElementType::Assembly => "Assemblies",
+        TabElementType::BillOfMaterials => "Bills of materials",
+        TabElementType::Blob => "Files",
+        TabElementType::Drawing => "Drawings",
+        TabElementType::FeatureStudio => "Feature studios",
+        TabElementType::PartStudio => "Part studios",
+        TabElementType::PublicationItem => "Publication items",
+        TabElementType::Table => "Tables",
+        TabElementType::VariableStudio => "Variable studios",
+        TabElementType::Unknown => "Unknown",
+    }
+}
+
+/// Fetches the parts of each part studio in `config`, passing them to `visit` in the order
+/// the studios are configured. The studios passed to `visit` have been resolved, so their
+/// IDs and display names are always present.
+pub async fn visit_synced_parts(
+    client: &OnShapeClient,
+    config: &SyncConfig,
+    mut visit: impl FnMut(&SyncedPartStudio, Vec<Part>),
+) -> Result<()> {
+    let config = config.resolved(client).await?;
+    let wvm = config.document.wvm();
+
+    for sync_part_studio in config.part_studios.iter() {
+        let studio_parts = client
+            .get_studio_parts(&config.document.id, &wvm, &sync_part_studio.id)
+            .await?;
+        visit(sync_part_studio, studio_parts);
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use indoc::indoc;
+
+    use super::*;
+    use crate::test_fixtures;
+
+    #[test]
+    fn test_render_tree() {
+        let document = test_fixtures::document();
+        let studio = |id: &str, name: &str| SyncedPartStudio {
+            display_name: name.into(),
+            id: id.into(),
+            name: None,
+            formats: None,
+            three_mf_path: None,
+            step_path: None,
+            stl_path: None,
+        };
+        let part = |id: &str, name: &str| Part {
+            name: name.into(),
+            part_id: id.into(),
+            element_id: "59628be740b2cf41a42e4c8a".into(),
+            microversion_id: "mv1".into(),
+            body_type: "solid".into(),
+        };
+        let studios = [
+            (
+                studio("59628be740b2cf41a42e4c8a", "Printables"),
+                vec![part("JHD", "Left Bracket"), part("JHHD", "Knob")],
+            ),
+            (studio("0d4b5a6e8c1f2a3b4c5d6e7f", "Hardware"), vec![]),
+        ];
+
+        assert_eq!(
+            render_tree(&document, &studios),
+            indoc! {"
+                Desk Organizer (6a1de9567f16cd7d1f564dbb)
+                ├── Printables (59628be740b2cf41a42e4c8a)
+                │   ├── Left Bracket  JHD   solid  left_bracket
+                │   └── Knob          JHHD  solid  knob
+                └── Hardware (0d4b5a6e8c1f2a3b4c5d6e7f)
+                    └── (no parts)
+            "}
+        );
+    }
+
+    #[test]
+    fn test_render_elements() {
+        let studio_id = ElementId::from("59628be740b2cf41a42e4c8a");
+        let drawing_id = ElementId::from("0d4b5a6e8c1f2a3b4c5d6e7f");
+        let hardware_id = ElementId::from("1e2f3a4b5c6d7e8f9a0b1c2d");
+        let entries = [
+            ElementEntry {
+                element_type: TabElementType::PartStudio,
+                id: &hardware_id,
+                name: "Hardware",
+                synced: false,
+            },
+            ElementEntry {
+                element_type: TabElementType::PartStudio,
+                id: &studio_id,
+                name: "Printables",
+                synced: true,
+            },
+            ElementEntry {
+                element_type: TabElementType::Drawing,
+                id: &drawing_id,
+                name: "Drawing 1",
+                synced: false,
+            },
+        ];
+
+        assert_eq!(
+            render_elements(&entries),
+            indoc! {"
+                Part studios:
+                  Hardware    1e2f3a4b5c6d7e8f9a0b1c2d
+                  Printables  59628be740b2cf41a42e4c8a  (synced)
+
+                Drawings:
+                  Drawing 1  0d4b5a6e8c1f2a3b4c5d6e7f
+            "}
+        );
+    }
+}
@@ -0,0 +1,81 @@
+use std::{collections::BTreeMap, fs};
+
+use anyhow::{Context, Result};
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    onshape::{
+        models::ExportFileFormat, DocumentId, ElementId, PartId, VersionId, WorkspaceId,
+    },
+    pull::write_atomically,
+};
+
+/// The name of the file, alongside offshape.toml, that records what was last pulled
+pub const STATE_FILE_NAME: &str = "offshape.lock";
+
+/// A record of the files written by the most recent pull, and the parts they came from. It
+/// is meant to be committed alongside the exported files.
+///
+/// This doubles as the manifest of files offshape owns. Pulls only ever delete files that
+/// appear here.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct PullState {
+    pub document_id: DocumentId,
+    pub workspace_id: WorkspaceId,
+    /// The version that was pulled, if the pull was made from a version rather than the
+    /// workspace's current state
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version_id: Option<VersionId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version_name: Option<String>,
+    /// Keyed by the file's path, relative to the directory containing offshape.toml
+    #[serde(default, rename = "file")]
+    pub files: BTreeMap<Utf8PathBuf, FileRecord>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct FileRecord {
+    pub studio_id: ElementId,
+    pub part_id: PartId,
+    pub part_name: String,
+    pub format: ExportFileFormat,
+    /// The part's microversion at the time it was exported
+    pub microversion_id: String,
+}
+
+impl PullState {
+    pub fn path(root: &Utf8Path) -> Utf8PathBuf {
+        root.join(STATE_FILE_NAME)
+    }
+
+    /// Loads the state stored in `root`, or an empty state if nothing has been pulled yet
+    pub fn load(root: &Utf8Path) -> Result<Self> {
+        let path = Self::path(root);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        toml::from_str(&fs::read_to_string(&path)?)
+            .with_context(|| format!("Could not parse {path}"))
+    }
+
+    pub fn save(&self, root: &Utf8Path) -> Result<()> {
+        let path = Self::path(root);
+        let contents = format!(
+            "# Written by offshape. Records the files produced by the last pull.\n\n{}",
+            toml::to_string(self)?
+        );
+        write_atomically(&path, contents.as_bytes(), |_| Ok(()))
+            .with_context(|| format!("Could not write {path}"))
+    }
+
+    /// Returns the recorded file at `path`, an absolute path within `root`
+    pub fn file(&self, root: &Utf8Path, path: &Utf8Path) -> Option<&FileRecord> {
+        self.files.get(&relative_path(root, path))
+    }
+}
+
+/// Expresses `path` relative to `root`, if it lies within it
+pub fn relative_path(root: &Utf8Path, path: &Utf8Path) -> Utf8PathBuf {
+    path.strip_prefix(root).unwrap_or(path).into()
+}
@@ -0,0 +1,352 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt::{self, Display, Formatter},
+};
+
+use anyhow::Result;
+use camino::Utf8PathBuf;
+use clap::Args;
+use serde::Serialize;
+
+use crate::{
+    config::SyncConfig,
+    onshape::{
+        environment_client,
+        models::{ExportFileFormat, Part},
+        ElementId, OnShapeClient, PartId,
+    },
+    pull::{exported_files, output_path, part_basename},
+    state::{relative_path, PullState},
+    table::Table,
+    GlobalOptions, OutputFormat,
+};
+
+#[derive(Args, Debug, Default)]
+pub struct StatusOptions {
+    #[clap(long, short, default_value_t = OutputFormat::default())]
+    pub format: OutputFormat,
+}
+
+/// How a file on disk compares to the part it is exported from
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StatusKind {
+    /// The part exists in OnShape, but hasn't been exported to this file
+    New,
+    /// The part has changed in OnShape since the file was pulled
+    Changed,
+    /// The file was pulled from a part that no longer exists in OnShape
+    Deleted,
+    /// The file doesn't correspond to any part in the configured part studios
+    Unexpected,
+    /// The file is named for a part, but offshape has no record of pulling it, so it's
+    /// unknown whether it is current
+    Untracked,
+    UpToDate,
+}
+
+impl Display for StatusKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            StatusKind::New => "new",
+            StatusKind::Changed => "changed",
+            StatusKind::Deleted => "deleted",
+            StatusKind::Unexpected => "unexpected",
+            StatusKind::Untracked => "untracked",
+            StatusKind::UpToDate => "up to date",
+        })
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct StatusEntry {
+    pub status: StatusKind,
+    /// The file's path, relative to the directory containing offshape.toml
+    pub path: Utf8PathBuf,
+    #[serde(serialize_with = "ExportFileFormat::serialize_extension")]
+    pub format: ExportFileFormat,
+    pub studio_id: Option<ElementId>,
+    pub part_id: Option<PartId>,
+    pub part_name: Option<String>,
+}
+
+/// The differences between the exported files on disk, and the parts in OnShape
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Status {
+    pub entries: Vec<StatusEntry>,
+}
+impl Status {
+    /// Returns `true` if every file is up to date
+    pub fn is_clean(&self) -> bool {
+        self.entries
+            .iter()
+            .all(|e| e.status == StatusKind::UpToDate)
+    }
+}
+
+/// Prints how the exported files on disk differ from the parts in OnShape
+pub async fn status(
+    config: SyncConfig,
+    global_options: GlobalOptions,
+    options: StatusOptions,
+) -> Result<()> {
+    let client = environment_client(&global_options, config.base_url.as_deref())?;
+    let status = local_status(&client, &config).await?;
+
+    match options.format {
+        OutputFormat::Friendly => {
+            if status.is_clean() {
+                println!("All {} exported files are up to date", status.entries.len());
+            }
+            for entry in status
+                .entries
+                .iter()
+                .filter(|e| e.status != StatusKind::UpToDate)
+            {
+                let status = format!("{}:", entry.status);
+                match entry.part_name {
+                    Some(ref name) => println!("{status:<12}{} ({name})", entry.path),
+                    None => println!("{status:<12}{}", entry.path),
+                }
+            }
+        }
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&status)?),
+        format => {
+            let mut table = Table::new(vec!["status", "path", "format", "part_id", "part"]);
+            for entry in status.entries.iter() {
+                table.push(vec![
+                    entry.status.to_string(),
+                    entry.path.to_string(),
+                    entry.format.extension(),
+                    entry
+                        .part_id
+                        .as_ref()
+                        .map(|id| id.to_string())
+                        .unwrap_or_default(),
+                    entry.part_name.clone().unwrap_or_default(),
+                ]);
+            }
+            print!("{}", table.render(format));
+        }
+    }
+    Ok(())
+}
+
+/// Compares the files in each configured format's path against the parts currently in
+/// the configured part studios, and the record of the last pull
+pub async fn local_status(client: &OnShapeClient, config: &SyncConfig) -> Result<Status> {
+    let config = &config.resolved(client).await?;
+    let wvm = config.document.wvm();
+    let state = PullState::load(&config.root)?;
+    if !state_matches(config, &state) {
+        log::warn!(
+            "offshape.lock records a pull from another document or workspace, so every file \
+             will be pulled again"
+        );
+    }
+
+    // Parts are listed from the same version of the document that pull exports
+    let mut parts: Vec<(ElementId, Part)> = vec![];
+    for studio in config.part_studios.iter() {
+        let studio_parts = client
+            .get_studio_parts(&config.document.id, &wvm, &studio.id)
+            .await?;
+        parts.extend(studio_parts.into_iter().map(|p| (studio.id.clone(), p)));
+    }
+
+    Ok(compare(config, &state, &parts))
+}
+
+/// Whether `state` records a pull from the configured document and workspace. Pull ignores
+/// the record of any other, and so re-exports every file.
+fn state_matches(config: &SyncConfig, state: &PullState) -> bool {
+    state.files.is_empty() ||
+        (state.document_id == config.document.id &&
+            state.workspace_id == config.document.workspace_id)
+}
+
+fn compare(config: &SyncConfig, state: &PullState, parts: &[(ElementId, Part)]) -> Status {
+    // Every file is changed if the pull recorded was from elsewhere
+    let state_matches = state_matches(config, state);
+    let remote_parts: HashSet<(&ElementId, &PartId)> = parts
+        .iter()
+        .map(|(studio_id, part)| (studio_id, &part.part_id))
+        .collect();
+
+    let mut entries = BTreeMap::new();
+    let mut expected = HashSet::new();
+    for (studio_id, part) in parts {
+        let Some(studio) = config.part_studios.iter().find(|s| s.id == *studio_id) else {
+            continue;
+        };
+        for (format, dir) in config.studio_outputs(studio) {
+            let path = output_path(&dir, &format, &part_basename(&part.name));
+            let status = match state.file(&config.root, &path) {
+                _ if !path.exists() => StatusKind::New,
+                _ if !state_matches => StatusKind::Changed,
+                Some(record) if record.microversion_id != part.microversion_id => {
+                    StatusKind::Changed
+                }
+                Some(_) => StatusKind::UpToDate,
+                None => StatusKind::Untracked,
+            };
+            let path = relative_path(&config.root, &path);
+            expected.insert(path.clone());
+            entries.insert(
+                path.clone(),
+                StatusEntry {
+                    status,
+                    path,
+                    format,
+                    studio_id: Some(studio_id.clone()),
+                    part_id: Some(part.part_id.clone()),
+                    part_name: Some(part.name.clone()),
+                },
+            );
+        }
+    }
+
+    for (format, dir) in config.output_dirs() {
+        for path in exported_files(&dir, &format) {
+            let path = relative_path(&config.root, &path);
+            if expected.contains(&path) {
+                continue;
+            }
+            let entry = match state.files.get(&path).filter(|_| state_matches) {
+                Some(record)
+                    if !remote_parts.contains(&(&record.studio_id, &record.part_id)) =>
+                {
+                    StatusEntry {
+                        status: StatusKind::Deleted,
+                        path: path.clone(),
+                        format,
+                        studio_id: Some(record.studio_id.clone()),
+                        part_id: Some(record.part_id.clone()),
+                        part_name: Some(record.part_name.clone()),
+                    }
+                }
+                _ => StatusEntry {
+                    status: StatusKind::Unexpected,
+                    path: path.clone(),
+                    format,
+                    studio_id: None,
+                    part_id: None,
+                    part_name: None,
+                },
+            };
+            entries.insert(path, entry);
+        }
+    }
+
+    let mut entries: Vec<StatusEntry> = entries.into_values().collect();
+    entries.sort_by(|a, b| a.status.cmp(&b.status).then_with(|| a.path.cmp(&b.path)));
+    Status { entries }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use indoc::indoc;
+
+    use super::*;
+    use crate::{state::FileRecord, test_fixtures::TempRoot};
+
+    #[test]
+    fn test_compare() {
+        let root = TempRoot::new();
+        let stl_dir = root.join("stl");
+        fs::create_dir_all(&stl_dir).unwrap();
+        for name in ["left_bracket", "knob", "old_part", "hand_made"] {
+            fs::write(stl_dir.join(format!("{name}.stl")), "solid").unwrap();
+        }
+
+        let mut config: SyncConfig = toml::from_str(indoc! {r#"
+            [document]
+            id = "6a1de9567f16cd7d1f564dbb"
+            workspace_id = "da2053ce82593768f41daab4"
+
+            [[part_studio]]
+            id = "59628be740b2cf41a42e4c8a"
+        "#})
+        .unwrap();
+        config.stl_path = Some(stl_dir.clone().into());
+        config.root = root.to_path_buf();
+
+        let studio_id = ElementId::from("59628be740b2cf41a42e4c8a");
+        let part = |id: &str, name: &str, microversion_id: &str| Part {
+            name: name.into(),
+            part_id: id.into(),
+            element_id: studio_id.clone(),
+            microversion_id: microversion_id.into(),
+            body_type: "solid".into(),
+        };
+        let record = |id: &str, name: &str, microversion_id: &str| FileRecord {
+            studio_id: studio_id.clone(),
+            part_id: id.into(),
+            part_name: name.into(),
+            format: ExportFileFormat::Stl,
+            microversion_id: microversion_id.into(),
+        };
+        let state = PullState {
+            document_id: config.document.id.clone(),
+            workspace_id: config.document.workspace_id.clone(),
+            files: [
+                (
+                    "stl/left_bracket.stl".into(),
+                    record("JHD", "Left Bracket", "mv1"),
+                ),
+                ("stl/knob.stl".into(), record("JHH", "Knob", "mv1")),
+                ("stl/old_part.stl".into(), record("JHL", "Old Part", "mv1")),
+            ]
+            .into(),
+            ..Default::default()
+        };
+        let parts = [
+            part("JHD", "Left Bracket", "mv1"),
+            part("JHH", "Knob", "mv2"),
+            part("JHP", "Right Bracket", "mv2"),
+        ]
+        .map(|p| (studio_id.clone(), p));
+
+        let status = compare(&config, &state, &parts);
+        // A record of another workspace is ignored
+        let other_state = PullState {
+            workspace_id: "0e1f2a3b4c5d6e7f8a9b0c1d".into(),
+            ..state.clone()
+        };
+        let other_status = compare(&config, &other_state, &parts);
+
+        let statuses = |status: &Status| -> Vec<(StatusKind, String)> {
+            status
+                .entries
+                .iter()
+                .map(|e| (e.status, e.path.to_string()))
+                .collect()
+        };
+        assert_eq!(
+            statuses(&status),
+            [
+                (StatusKind::New, "stl/right_bracket.stl".into()),
+                (StatusKind::Changed, "stl/knob.stl".into()),
+                (StatusKind::Deleted, "stl/old_part.stl".into()),
+                (StatusKind::Unexpected, "stl/hand_made.stl".into()),
+                (StatusKind::UpToDate, "stl/left_bracket.stl".into()),
+            ]
+        );
+        assert_eq!(
+            statuses(&other_status),
+            [
+                (StatusKind::New, "stl/right_bracket.stl".into()),
+                (StatusKind::Changed, "stl/knob.stl".into()),
+                (StatusKind::Changed, "stl/left_bracket.stl".into()),
+                (StatusKind::Unexpected, "stl/hand_made.stl".into()),
+                (StatusKind::Unexpected, "stl/old_part.stl".into()),
+            ]
+        );
+
+        // Formats are spelled as they are in offshape.toml
+        let json = serde_json::to_value(&status.entries[0]).unwrap();
+        assert_eq!(json["format"], "stl");
+    }
+}
@@ -0,0 +1,124 @@
+use std::borrow::Cow;
+
+use itertools::Itertools;
+
+use crate::OutputFormat;
+
+/// Rows of text under a header, rendered as aligned columns or as CSV
+#[derive(Clone, Debug)]
+pub(crate) struct Table {
+    headers: Vec<&'static str>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn new(headers: Vec<&'static str>) -> Self {
+        Self {
+            headers,
+            rows: vec![],
+        }
+    }
+
+    pub fn push(&mut self, row: Vec<String>) {
+        debug_assert_eq!(row.len(), self.headers.len());
+        self.rows.push(row);
+    }
+
+    /// Renders the table in `format`, which must be [`OutputFormat::Table`] or
+    /// [`OutputFormat::Csv`]
+    pub fn render(&self, format: OutputFormat) -> String {
+        match format {
+            OutputFormat::Csv => self.to_csv(),
+            _ => self.to_aligned(),
+        }
+    }
+
+    /// Pads each column to the width of its widest cell. The last column isn't padded.
+    pub fn to_aligned(&self) -> String {
+        let headers: Vec<String> = self.headers.iter().map(|h| h.to_uppercase()).collect();
+        let widths: Vec<usize> = (0..self.headers.len())
+            .map(|i| {
+                self.rows
+                    .iter()
+                    .map(|row| &row[i])
+                    .chain([&headers[i]])
+                    .map(|cell| cell.chars().count())
+                    .max()
+                    .unwrap_or_default()
+            })
+            .collect();
+
+        [&headers]
+            .into_iter()
+            .chain(self.rows.iter())
+            .map(|row| {
+                let line = row
+                    .iter()
+                    .zip(widths.iter())
+                    .map(|(cell, width)| format!("{cell:<width$}"))
+                    .join("  ");
+                format!("{}\n", line.trim_end())
+            })
+            .collect()
+    }
+
+    pub fn to_csv(&self) -> String {
+        [self.headers.iter().map(|h| csv_field(h)).join(",")]
+            .into_iter()
+            .chain(
+                self.rows
+                    .iter()
+                    .map(|row| row.iter().map(|cell| csv_field(cell)).join(",")),
+            )
+            .map(|line| format!("{line}\n"))
+            .collect()
+    }
+}
+
+/// Quotes a CSV field if it contains a delimiter, quote or line break
+fn csv_field(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use indoc::indoc;
+
+    use super::*;
+
+    #[test]
+    fn test_render_table() {
+        let mut table = Table::new(vec!["part", "part_id", "basename"]);
+        table.push(vec![
+            "Left Bracket".into(),
+            "JHD".into(),
+            "left_bracket".into(),
+        ]);
+        table.push(vec![
+            "Knob, large".into(),
+            "JHH".into(),
+            "knob,_large".into(),
+        ]);
+
+        assert_eq!(
+            table.to_aligned(),
+            indoc! {"
+                PART          PART_ID  BASENAME
+                Left Bracket  JHD      left_bracket
+                Knob, large   JHH      knob,_large
+            "}
+        );
+        assert_eq!(
+            table.to_csv(),
+            indoc! {r#"
+                part,part_id,basename
+                Left Bracket,JHD,left_bracket
+                "Knob, large",JHH,"knob,_large"
+            "#}
+        );
+    }
+}
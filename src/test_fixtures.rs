@@ -0,0 +1,47 @@
+//! Fixtures shared by the tests of several modules
+
+use std::ops::Deref;
+
+use camino::{Utf8Path, Utf8PathBuf};
+use tempfile::TempDir;
+
+use crate::onshape::models::{Document, Workspace};
+
+/// The document that tests sync from
+pub fn document() -> Document {
+    Document {
+        id: "6a1de9567f16cd7d1f564dbb".into(),
+        name: "Desk Organizer".into(),
+        default_workspace: Workspace {
+            id: "da2053ce82593768f41daab4".into(),
+            name: "Main".into(),
+            href: "https://cad.onshape.com/api/documents/d/6a1de/w/da205"
+                .parse()
+                .unwrap(),
+        },
+    }
+}
+
+/// An empty directory, which is removed along with its contents when dropped
+pub struct TempRoot {
+    _dir: TempDir,
+    path: Utf8PathBuf,
+}
+impl TempRoot {
+    pub fn new() -> Self {
+        let dir = tempfile::Builder::new()
+            .prefix("offshape-")
+            .tempdir()
+            .unwrap();
+        // Canonicalized, so that paths built from it match those offshape resolves
+        let path = Utf8PathBuf::try_from(dir.path().canonicalize().unwrap()).unwrap();
+        Self { _dir: dir, path }
+    }
+}
+impl Deref for TempRoot {
+    type Target = Utf8Path;
+
+    fn deref(&self) -> &Utf8Path {
+        &self.path
+    }
+}
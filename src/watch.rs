@@ -0,0 +1,115 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use clap::Args;
+use tokio::{signal::ctrl_c, time::sleep};
+
+use crate::{
+    config::SyncConfig,
+    hooks::run_hook,
+    onshape::{environment_client, OnShapeClient},
+    pull::{pull, PullOptions},
+    report::ReporterKind,
+    GlobalOptions,
+};
+
+#[derive(Args, Debug)]
+pub struct WatchOptions {
+    /// How often to check the document for changes, in seconds
+    #[arg(long, value_name = "SECONDS", default_value_t = 10)]
+    pub interval: u64,
+    /// A shell command to run after each pull, from the directory containing offshape.toml.
+    /// `OFFSHAPE_MICROVERSION` is set to the microversion that was pulled.
+    #[arg(long, value_name = "COMMAND")]
+    pub exec: Option<String>,
+    /// How progress is reported while pulling
+    #[arg(long, default_value_t = ReporterKind::Log)]
+    pub reporter: ReporterKind,
+    /// Moves the files that exported successfully into place, even if others failed
+    #[arg(long)]
+    pub allow_partial: bool,
+}
+
+/// Pulls the parts that have changed whenever the workspace's microversion changes, until
+/// interrupted
+pub async fn watch(
+    config: SyncConfig,
+    global_options: GlobalOptions,
+    options: WatchOptions,
+) -> Result<()> {
+    if config.document.pinned_version.is_some() {
+        return Err(anyhow!(
+            "[document] version pins the config to a version, which never changes. Remove it \
+             to watch the workspace."
+        ));
+    }
+    let client = environment_client(&global_options, config.base_url.as_deref())?;
+    tokio::select! {
+        res = watch_workspace(&client, &config, &options) => res,
+        _ = ctrl_c() => Ok(()),
+    }
+}
+
+async fn watch_workspace(
+    client: &OnShapeClient,
+    config: &SyncConfig,
+    options: &WatchOptions,
+) -> Result<()> {
+    let pull_options = PullOptions {
+        incremental: true,
+        allow_partial: options.allow_partial,
+        reporter: options.reporter,
+        ..Default::default()
+    };
+    let interval = Duration::from_secs(options.interval);
+    let document = &config.document;
+
+    log::info!(
+        "Watching for changes every {}s. Press Ctrl-C to stop.",
+        options.interval
+    );
+    let mut pulled_microversion = None;
+    loop {
+        let microversion = match client
+            .get_current_microversion(&document.id, &document.workspace_id)
+            .await
+        {
+            Ok(microversion) => microversion,
+            Err(e) => {
+                log::warn!("Could not check the document for changes: {e:#}");
+                sleep(interval).await;
+                continue;
+            }
+        };
+
+        if pulled_microversion.as_ref() != Some(&microversion) {
+            if pulled_microversion.is_some() {
+                log::info!("The document changed. Pulling...");
+            }
+            let reporter = options.reporter.reporter();
+            let res = pull(client, config, &pull_options, reporter.as_ref()).await;
+            reporter.finish();
+            match res {
+                Ok(summary) => {
+                    log::info!("Pulled {} changed files", summary.written.len());
+                    pulled_microversion = Some(microversion.clone());
+                    if let Some(ref command) = options.exec {
+                        if let Err(e) = run_hook(
+                            command,
+                            &config.root,
+                            &[("OFFSHAPE_MICROVERSION", &microversion)],
+                        )
+                        .await
+                        {
+                            log::warn!("{e:#}");
+                        }
+                    }
+                }
+                // Retried at the next check
+                Err(e) => log::warn!("Pull failed: {e:#}"),
+            }
+        }
+
+        sleep(interval).await;
+    }
+}